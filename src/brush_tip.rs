@@ -0,0 +1,151 @@
+use ::image::{imageops, GrayImage, Luma, Rgb, RgbImage, RgbaImage};
+
+/// Largest side of a tip image, bigger images are scaled down when loaded
+const MAX_TIP_SIZE: u32 = 256;
+
+/// Shape stamped along brush strokes
+#[derive(Clone)]
+pub enum BrushTip {
+    /// Disc whose edge softens as the brush hardness goes down
+    Round,
+    Square,
+    /// Disc fading out towards its edge
+    SoftRound,
+    /// Disc with grainy coverage, like chalk
+    Textured,
+    /// Tip loaded from an image file. Dark pixels of grayscale tips paint with
+    /// the brush color, color tips keep their own colors and paint where they are opaque
+    Image {
+        name: String,
+        image: RgbaImage,
+        colored: bool,
+    },
+}
+
+/// Tips that are always available in the brush panel
+pub fn builtin_tips() -> Vec<BrushTip> {
+    vec![
+        BrushTip::Round,
+        BrushTip::Square,
+        BrushTip::SoftRound,
+        BrushTip::Textured,
+    ]
+}
+
+/// Repeatable noise for the textured tip, from 0 to 255
+fn grain(x: u32, y: u32) -> u8 {
+    let mut h = x.wrapping_mul(0x9e37_79b1) ^ y.wrapping_mul(0x85eb_ca77);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    (h & 0xff) as u8
+}
+
+impl BrushTip {
+    pub fn load(path: &std::path::Path) -> Result<Self, ::image::ImageError> {
+        let img = ::image::open(path)?;
+        let colored = img.color().has_color();
+        let img = if img.width() > MAX_TIP_SIZE || img.height() > MAX_TIP_SIZE {
+            img.thumbnail(MAX_TIP_SIZE, MAX_TIP_SIZE)
+        } else {
+            img
+        };
+        let name = path
+            .file_stem()
+            .map_or_else(|| "Image".to_string(), |s| s.to_string_lossy().into_owned());
+
+        Ok(BrushTip::Image {
+            name,
+            image: img.to_rgba8(),
+            colored,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            BrushTip::Round => "Round",
+            BrushTip::Square => "Square",
+            BrushTip::SoftRound => "Soft Round",
+            BrushTip::Textured => "Textured",
+            BrushTip::Image { name, .. } => name,
+        }
+    }
+
+    /// Colors and coverage of one dab whose longer side is `diameter` pixels.
+    /// `hardness` from 0 to 1 is the part of the round tip radius painted at full
+    /// coverage. Tips without colors of their own are painted with `color`
+    pub fn render(
+        &self,
+        diameter: i32,
+        hardness: f64,
+        color: (u8, u8, u8),
+    ) -> (RgbImage, GrayImage) {
+        let d = diameter.max(1) as u32;
+        let fill = |w, h| RgbImage::from_pixel(w, h, Rgb([color.0, color.1, color.2]));
+
+        // Distance of the pixel center from the center of the dab, 1 at the edge
+        let radius = d as f64 / 2.0;
+        let distance =
+            |x: u32, y: u32| (x as f64 + 0.5 - radius).hypot(y as f64 + 0.5 - radius) / radius;
+
+        match self {
+            BrushTip::Round => (
+                fill(d, d),
+                GrayImage::from_fn(d, d, |x, y| {
+                    let t = distance(x, y);
+                    let coverage = if t <= hardness || d == 1 {
+                        1.0
+                    } else if t >= 1.0 {
+                        0.0
+                    } else {
+                        // Smooth step from the hard core to the edge
+                        let s = (1.0 - t) / (1.0 - hardness);
+                        s * s * (3.0 - 2.0 * s)
+                    };
+                    Luma([(coverage * 255.0).round() as u8])
+                }),
+            ),
+            BrushTip::Square => (fill(d, d), GrayImage::from_pixel(d, d, Luma([255]))),
+            BrushTip::SoftRound => (
+                fill(d, d),
+                GrayImage::from_fn(d, d, |x, y| {
+                    let t = (1.0 - distance(x, y).powi(2)).max(0.0);
+                    Luma([(t * t * 255.0).round() as u8])
+                }),
+            ),
+            BrushTip::Textured => (
+                fill(d, d),
+                GrayImage::from_fn(d, d, |x, y| {
+                    let inside = distance(x, y) <= 1.0 || d == 1;
+                    Luma([inside as u8 * grain(x, y).saturating_add(64)])
+                }),
+            ),
+            BrushTip::Image { image, colored, .. } => {
+                // Longer side of the image fits the diameter
+                let scale = d as f64 / image.width().max(image.height()) as f64;
+                let w = ((image.width() as f64 * scale).round() as u32).max(1);
+                let h = ((image.height() as f64 * scale).round() as u32).max(1);
+                let image = imageops::resize(image, w, h, imageops::FilterType::Triangle);
+
+                let coverage = GrayImage::from_fn(w, h, |x, y| {
+                    let p = image.get_pixel(x, y).0;
+                    if *colored {
+                        Luma([p[3]])
+                    } else {
+                        let darkness = 255 - p[0] as u32;
+                        Luma([(darkness * p[3] as u32 / 255) as u8])
+                    }
+                });
+                let colors = if *colored {
+                    RgbImage::from_fn(w, h, |x, y| {
+                        let p = image.get_pixel(x, y).0;
+                        Rgb([p[0], p[1], p[2]])
+                    })
+                } else {
+                    fill(w, h)
+                };
+                (colors, coverage)
+            }
+        }
+    }
+}
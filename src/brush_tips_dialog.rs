@@ -0,0 +1,180 @@
+use fltk::{prelude::*, *};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::brush_tip::{self, BrushTip};
+
+const PREVIEW_SIZE: i32 = 80;
+
+#[derive(Copy, Clone)]
+enum ModalResult {
+    Ok,
+    Cancel,
+}
+
+/// Brush panel listing the built-in tips and the ones loaded from image files
+pub struct BrushTipsDialog {
+    window: window::Window,
+    tips_browser: browser::HoldBrowser,
+    tips: Rc<RefCell<Vec<BrushTip>>>,
+    modal_result: Rc<RefCell<ModalResult>>,
+}
+
+impl BrushTipsDialog {
+    pub fn new() -> Self {
+        let modal_result = Rc::from(RefCell::from(ModalResult::Cancel));
+        let tips = Rc::from(RefCell::from(brush_tip::builtin_tips()));
+
+        let mut window = window::Window::default()
+            .with_label("Brush Tips")
+            .with_size(390, 220);
+
+        let mut tips_browser = browser::HoldBrowser::default()
+            .with_label("Tips:")
+            .with_size(180, 180)
+            .with_pos(5, 30);
+        tips_browser.set_align(enums::Align::TopLeft);
+        for tip in tips.borrow().iter() {
+            tips_browser.add(tip.name());
+        }
+        tips_browser.select(1);
+
+        // Dab of the selected tip in black on white
+        let mut preview = frame::Frame::default()
+            .with_size(PREVIEW_SIZE + 4, PREVIEW_SIZE + 4)
+            .with_pos(195, 30);
+        preview.set_frame(enums::FrameType::DownBox);
+        preview.draw({
+            let tips = tips.clone();
+            let tips_browser = tips_browser.clone();
+            move |f| {
+                let tips = tips.borrow();
+                let Some(tip) = tips.get((tips_browser.value() - 1).max(0) as usize) else {
+                    return;
+                };
+                let (_, coverage) = tip.render(PREVIEW_SIZE, 1.0, (0, 0, 0));
+                let data = coverage
+                    .pixels()
+                    .flat_map(|p| [255 - p.0[0]; 3])
+                    .collect::<Vec<u8>>();
+                let (w, h) = (coverage.width() as i32, coverage.height() as i32);
+                draw::draw_rect_fill(
+                    f.x() + 2,
+                    f.y() + 2,
+                    PREVIEW_SIZE,
+                    PREVIEW_SIZE,
+                    enums::Color::White,
+                );
+                let _ = draw::draw_image(
+                    &data,
+                    f.x() + 2 + (PREVIEW_SIZE - w) / 2,
+                    f.y() + 2 + (PREVIEW_SIZE - h) / 2,
+                    w,
+                    h,
+                    enums::ColorDepth::Rgb8,
+                );
+            }
+        });
+
+        tips_browser.set_callback({
+            let mut preview = preview.clone();
+            move |_| preview.redraw()
+        });
+
+        let mut ok_btn = button::Button::default()
+            .with_label("&OK")
+            .with_size(75, 25)
+            .with_pos(305, 30);
+
+        ok_btn.set_callback({
+            let mut window = window.clone();
+            let modal_result = modal_result.clone();
+            move |_| {
+                *modal_result.borrow_mut() = ModalResult::Ok;
+                window.hide();
+            }
+        });
+
+        let mut cancel_btn = button::Button::default()
+            .with_label("&Cancel")
+            .with_size(75, 25)
+            .with_pos(305, 60);
+
+        cancel_btn.set_callback({
+            let mut window = window.clone();
+            move |_| {
+                window.hide();
+            }
+        });
+
+        let mut load_btn = button::Button::default()
+            .with_label("&Load...")
+            .with_size(75, 25)
+            .with_pos(305, 100);
+        load_btn.set_tooltip("Grayscale images paint with the foreground color");
+
+        load_btn.set_callback({
+            let tips = tips.clone();
+            let mut tips_browser = tips_browser.clone();
+            let mut preview = preview.clone();
+            move |_| {
+                let mut dlg = dialog::FileDialog::new(dialog::FileDialogType::BrowseFile);
+                dlg.set_title("Load Brush Tip");
+                dlg.set_filter("Images\t*.{bmp,jpg,jpeg,gif,tif,tiff,png}");
+                dlg.show();
+
+                let filename = dlg.filename();
+                if filename.to_string_lossy().is_empty() {
+                    return;
+                }
+                match BrushTip::load(&filename) {
+                    Ok(tip) => {
+                        tips_browser.add(tip.name());
+                        tips_browser.select(tips_browser.size());
+                        tips.borrow_mut().push(tip);
+                        preview.redraw();
+                    }
+                    Err(err) => dialog::alert_default(&format!(
+                        "Cannot load brush tip from file {}: {}",
+                        filename.display(),
+                        err
+                    )),
+                }
+            }
+        });
+
+        window.end();
+
+        window.make_modal(true);
+
+        Self {
+            window,
+            tips_browser,
+            tips,
+            modal_result,
+        }
+    }
+
+    /// Lets the user pick a brush tip, returns `None` if the panel is cancelled
+    pub fn show(&mut self) -> Option<BrushTip> {
+        *self.modal_result.borrow_mut() = ModalResult::Cancel;
+        let current = self.tips_browser.value();
+
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+        }
+
+        match *self.modal_result.borrow() {
+            ModalResult::Ok => {
+                let index = self.tips_browser.value() - 1;
+                self.tips.borrow().get(index.max(0) as usize).cloned()
+            }
+            ModalResult::Cancel => {
+                self.tips_browser.select(current);
+                None
+            }
+        }
+    }
+}
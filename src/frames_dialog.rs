@@ -0,0 +1,123 @@
+use fltk::{prelude::*, *};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::multi_frame::ImageFrame;
+
+#[derive(Copy, Clone)]
+enum ModalResult {
+    Ok,
+    Cancel,
+}
+
+pub struct FramesDialog {
+    window: window::Window,
+    frames_browser: browser::MultiBrowser,
+    modal_result: Rc<RefCell<ModalResult>>,
+}
+
+impl FramesDialog {
+    pub fn new() -> Self {
+        let modal_result = Rc::from(RefCell::from(ModalResult::Cancel));
+
+        let mut window = window::Window::default()
+            .with_label("Import Frames")
+            .with_size(350, 220);
+
+        let mut frames_browser = browser::MultiBrowser::default()
+            .with_label("Frames to import:")
+            .with_size(250, 180)
+            .with_pos(5, 30);
+        frames_browser.set_align(enums::Align::TopLeft);
+
+        let mut ok_btn = button::Button::default()
+            .with_label("&OK")
+            .with_size(75, 25)
+            .with_pos(265, 30);
+
+        ok_btn.set_callback({
+            let mut window = window.clone();
+            let modal_result = modal_result.clone();
+            move |_| {
+                *modal_result.borrow_mut() = ModalResult::Ok;
+                window.hide();
+            }
+        });
+
+        let mut cancel_btn = button::Button::default()
+            .with_label("&Cancel")
+            .with_size(75, 25)
+            .with_pos(265, 60);
+
+        cancel_btn.set_callback({
+            let mut window = window.clone();
+            move |_| {
+                window.hide();
+            }
+        });
+
+        let mut select_all_btn = button::Button::default()
+            .with_label("Select &All")
+            .with_size(75, 25)
+            .with_pos(265, 100);
+
+        select_all_btn.set_callback({
+            let mut frames_browser = frames_browser.clone();
+            move |_| {
+                for line in 1..=frames_browser.size() {
+                    frames_browser.select(line);
+                }
+            }
+        });
+
+        window.end();
+
+        window.make_modal(true);
+
+        Self {
+            window,
+            frames_browser,
+            modal_result,
+        }
+    }
+
+    /// Lets the user pick frames to import, returns indices of the selected frames
+    pub fn show(&mut self, frames: &[ImageFrame]) -> Option<Vec<usize>> {
+        *self.modal_result.borrow_mut() = ModalResult::Cancel;
+
+        self.frames_browser.clear();
+        for (i, frame) in frames.iter().enumerate() {
+            let mut line = format!(
+                "Frame {} ({} x {})",
+                i + 1,
+                frame.image.width(),
+                frame.image.height()
+            );
+            if frame.delay_ms > 0 {
+                line += &format!(", {} ms", frame.delay_ms);
+            }
+            self.frames_browser.add(&line);
+            self.frames_browser.select(i as i32 + 1);
+        }
+
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+        }
+
+        match *self.modal_result.borrow() {
+            ModalResult::Ok => {
+                let selected = (0..frames.len())
+                    .filter(|i| self.frames_browser.selected(*i as i32 + 1))
+                    .collect::<Vec<usize>>();
+                if selected.is_empty() {
+                    None
+                } else {
+                    Some(selected)
+                }
+            }
+            ModalResult::Cancel => None,
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use ::image::{GrayImage, RgbImage};
+
+use std::collections::HashMap;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+pub type PaletteColor = [u8; 3];
+
+const MAX_PALETTE_SIZE: usize = 256;
+
+#[derive(Clone)]
+pub enum PaletteSource {
+    /// Every color used in the image, fails if there are more than 256 of them
+    Exact,
+    /// Colors read from a palette file (GIMP .gpl, JASC .pal, Adobe .act or hex list)
+    File(PathBuf),
+    /// Palette of the given size built with NeuQuant
+    Quantized(usize),
+}
+
+#[derive(Copy, Clone)]
+pub enum Dithering {
+    None,
+    FloydSteinberg,
+    Ordered,
+}
+
+#[derive(Clone)]
+pub struct IndexedOptions {
+    pub palette: PaletteSource,
+    pub dithering: Dithering,
+    pub transparent_index: Option<u8>,
+}
+
+/// Saves an image as a paletted PNG or GIF, picking the format from the file extension.
+/// With a transparent index set, pixels that are mostly transparent in `alpha` use that index
+/// # Errors
+/// Errors when the palette cannot be built or the file cannot be written
+pub fn save_indexed(
+    img: &RgbImage,
+    alpha: Option<&GrayImage>,
+    path: &Path,
+    options: &IndexedOptions,
+) -> Result<(), String> {
+    let palette = build_palette(img, &options.palette)?;
+
+    if let Some(index) = options.transparent_index {
+        if index as usize >= palette.len() {
+            return Err(format!(
+                "Transparent index {} is out of range for a palette of {} colors",
+                index,
+                palette.len()
+            ));
+        }
+    }
+
+    let mut indices = map_to_palette(img, &palette, options.dithering);
+
+    if let (Some(index), Some(alpha)) = (options.transparent_index, alpha) {
+        for (i, a) in indices.iter_mut().zip(alpha.iter()) {
+            if *a < 128 {
+                *i = index;
+            }
+        }
+    }
+
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => write_png(img, &palette, &indices, options.transparent_index, path),
+        "gif" => write_gif(img, &palette, &indices, options.transparent_index, path),
+        _ => Err("Indexed export supports only PNG and GIF files".to_string()),
+    }
+}
+
+pub fn build_palette(img: &RgbImage, source: &PaletteSource) -> Result<Vec<PaletteColor>, String> {
+    match source {
+        PaletteSource::Exact => {
+            let mut palette = Vec::new();
+            let mut seen = HashMap::new();
+            for p in img.pixels() {
+                if seen.insert(p.0, ()).is_none() {
+                    palette.push(p.0);
+                    if palette.len() > MAX_PALETTE_SIZE {
+                        return Err(format!(
+                            "Image uses more than {} colors, choose a quantized palette instead",
+                            MAX_PALETTE_SIZE
+                        ));
+                    }
+                }
+            }
+            Ok(palette)
+        }
+        PaletteSource::File(path) => {
+            let palette = load_palette(path)?;
+            if palette.is_empty() {
+                return Err(format!("Palette file {} has no colors", path.display()));
+            }
+            if palette.len() > MAX_PALETTE_SIZE {
+                return Err(format!(
+                    "Palette file {} has {} colors, at most {} are supported",
+                    path.display(),
+                    palette.len(),
+                    MAX_PALETTE_SIZE
+                ));
+            }
+            Ok(palette)
+        }
+        PaletteSource::Quantized(colors) => {
+            let colors = (*colors).clamp(2, MAX_PALETTE_SIZE);
+            let rgba = img
+                .pixels()
+                .flat_map(|p| [p.0[0], p.0[1], p.0[2], 255])
+                .collect::<Vec<u8>>();
+            let quant = color_quant::NeuQuant::new(10, colors, &rgba);
+            Ok(quant
+                .color_map_rgb()
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect())
+        }
+    }
+}
+
+/// Reads a palette file, recognized formats are GIMP .gpl, JASC .pal, Adobe .act
+/// and plain text files with one hex color per line
+pub fn load_palette(path: &Path) -> Result<Vec<PaletteColor>, String> {
+    let data = std::fs::read(path)
+        .map_err(|e| format!("Cannot read palette file {}. Error: {}", path.display(), e))?;
+
+    let is_act = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("act"));
+    if is_act {
+        return parse_act_palette(&data);
+    }
+
+    let text = String::from_utf8_lossy(&data);
+    let mut lines = text.lines().map(str::trim);
+    match lines.next() {
+        Some("GIMP Palette") => Ok(lines
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .filter(|l| !l.starts_with("Name:") && !l.starts_with("Columns:"))
+            .filter_map(parse_decimal_color)
+            .collect()),
+        Some("JASC-PAL") => Ok(lines.skip(2).filter_map(parse_decimal_color).collect()),
+        _ => Ok(text.lines().filter_map(parse_hex_color).collect()),
+    }
+}
+
+fn parse_act_palette(data: &[u8]) -> Result<Vec<PaletteColor>, String> {
+    if data.len() < MAX_PALETTE_SIZE * 3 {
+        return Err("Adobe color table should contain 768 bytes".to_string());
+    }
+
+    // Optional trailer holds the number of used colors
+    let count = if data.len() >= MAX_PALETTE_SIZE * 3 + 2 {
+        let count = u16::from_be_bytes([data[768], data[769]]) as usize;
+        if count == 0 || count > MAX_PALETTE_SIZE {
+            MAX_PALETTE_SIZE
+        } else {
+            count
+        }
+    } else {
+        MAX_PALETTE_SIZE
+    };
+
+    Ok(data[..count * 3]
+        .chunks_exact(3)
+        .map(|c| [c[0], c[1], c[2]])
+        .collect())
+}
+
+fn parse_decimal_color(line: &str) -> Option<PaletteColor> {
+    let mut values = line.split_whitespace().map(|v| v.parse::<u8>().ok());
+    Some([values.next()??, values.next()??, values.next()??])
+}
+
+fn parse_hex_color(line: &str) -> Option<PaletteColor> {
+    let hex = line.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+fn color_distance(a: [i32; 3], b: &PaletteColor) -> i32 {
+    let dr = a[0] - b[0] as i32;
+    let dg = a[1] - b[1] as i32;
+    let db = a[2] - b[2] as i32;
+    dr * dr + dg * dg + db * db
+}
+
+fn nearest_index(c: [i32; 3], palette: &[PaletteColor]) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| color_distance(c, p))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Maps every pixel of the image to an index in the palette
+pub fn map_to_palette(img: &RgbImage, palette: &[PaletteColor], dithering: Dithering) -> Vec<u8> {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let mut indices = vec![0u8; w * h];
+
+    match dithering {
+        Dithering::None => {
+            let mut cache = HashMap::new();
+            for (i, p) in img.pixels().enumerate() {
+                indices[i] = *cache.entry(p.0).or_insert_with(|| {
+                    let c = [p.0[0] as i32, p.0[1] as i32, p.0[2] as i32];
+                    nearest_index(c, palette) as u8
+                });
+            }
+        }
+        Dithering::FloydSteinberg => {
+            // Quantization error of the current and the next row
+            let mut errors = vec![[0i32; 3]; w + 2];
+            let mut next_errors = vec![[0i32; 3]; w + 2];
+            for y in 0..h {
+                for x in 0..w {
+                    let p = img.get_pixel(x as u32, y as u32).0;
+                    let e = errors[x + 1];
+                    let c = [
+                        (p[0] as i32 + e[0] / 16).clamp(0, 255),
+                        (p[1] as i32 + e[1] / 16).clamp(0, 255),
+                        (p[2] as i32 + e[2] / 16).clamp(0, 255),
+                    ];
+                    let index = nearest_index(c, palette);
+                    indices[y * w + x] = index as u8;
+
+                    let q = palette[index];
+                    for k in 0..3 {
+                        let diff = c[k] - q[k] as i32;
+                        errors[x + 2][k] += diff * 7;
+                        next_errors[x][k] += diff * 3;
+                        next_errors[x + 1][k] += diff * 5;
+                        next_errors[x + 2][k] += diff;
+                    }
+                }
+                std::mem::swap(&mut errors, &mut next_errors);
+                next_errors.iter_mut().for_each(|e| *e = [0; 3]);
+            }
+        }
+        Dithering::Ordered => {
+            const BAYER: [[i32; 4]; 4] =
+                [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+            // Spread the threshold over the average distance between palette levels
+            let spread = (255.0 / (palette.len() as f64).cbrt()) as i32;
+            for y in 0..h {
+                for x in 0..w {
+                    let p = img.get_pixel(x as u32, y as u32).0;
+                    let t = (BAYER[y % 4][x % 4] * 2 - 15) * spread / 32;
+                    let c = [
+                        (p[0] as i32 + t).clamp(0, 255),
+                        (p[1] as i32 + t).clamp(0, 255),
+                        (p[2] as i32 + t).clamp(0, 255),
+                    ];
+                    indices[y * w + x] = nearest_index(c, palette) as u8;
+                }
+            }
+        }
+    }
+
+    indices
+}
+
+fn write_png(
+    img: &RgbImage,
+    palette: &[PaletteColor],
+    indices: &[u8],
+    transparent_index: Option<u8>,
+    path: &Path,
+) -> Result<(), String> {
+    let bits: usize = match palette.len() {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    };
+    let depth = match bits {
+        1 => png::BitDepth::One,
+        2 => png::BitDepth::Two,
+        4 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+
+    // Pack indices MSB first, every row starts on a byte boundary
+    let w = img.width() as usize;
+    let row_bytes = (w * bits).div_ceil(8);
+    let mut data = vec![0u8; row_bytes * img.height() as usize];
+    for (y, row) in indices.chunks_exact(w).enumerate() {
+        for (x, index) in row.iter().enumerate() {
+            let bit = x * bits;
+            let shift = 8 - bits - bit % 8;
+            data[y * row_bytes + bit / 8] |= index << shift;
+        }
+    }
+
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), img.width(), img.height());
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_palette(palette.concat());
+    if let Some(index) = transparent_index {
+        let mut trns = vec![255u8; index as usize + 1];
+        trns[index as usize] = 0;
+        encoder.set_trns(trns);
+    }
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&data).map_err(|e| e.to_string())
+}
+
+fn write_gif(
+    img: &RgbImage,
+    palette: &[PaletteColor],
+    indices: &[u8],
+    transparent_index: Option<u8>,
+    path: &Path,
+) -> Result<(), String> {
+    let (w, h) = (img.width(), img.height());
+    if w > u16::MAX as u32 || h > u16::MAX as u32 {
+        return Err("Image is too large for the GIF format".to_string());
+    }
+
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder =
+        gif::Encoder::new(BufWriter::new(file), w as u16, h as u16, &palette.concat())
+            .map_err(|e| e.to_string())?;
+
+    let frame = gif::Frame::from_indexed_pixels(w as u16, h as u16, indices, transparent_index);
+    encoder.write_frame(&frame).map_err(|e| e.to_string())
+}
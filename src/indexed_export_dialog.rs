@@ -0,0 +1,168 @@
+use fltk::{prelude::*, *};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::indexed_export::{Dithering, IndexedOptions, PaletteSource};
+
+#[derive(Copy, Clone)]
+enum ModalResult {
+    Ok,
+    Cancel,
+}
+
+const DEFAULT_COLORS: i32 = 256;
+
+pub struct IndexedExportDialog {
+    window: window::Window,
+    palette_choice: menu::Choice,
+    colors_input: input::IntInput,
+    palette_file_input: input::Input,
+    dithering_choice: menu::Choice,
+    transparent_input: input::IntInput,
+    modal_result: Rc<RefCell<ModalResult>>,
+}
+
+impl IndexedExportDialog {
+    pub fn new() -> Self {
+        let modal_result = Rc::from(RefCell::from(ModalResult::Cancel));
+
+        let mut window = window::Window::default()
+            .with_label("Export Indexed")
+            .with_size(420, 170);
+
+        let mut palette_choice = menu::Choice::default()
+            .with_label("Palette: ")
+            .with_size(220, 25)
+            .with_pos(110, 10);
+        palette_choice.add_choice("Exact colors used|Quantized|From file");
+        palette_choice.set_value(0);
+
+        let colors_input = input::IntInput::default()
+            .with_label("Colors: ")
+            .with_size(75, 25)
+            .with_pos(110, 40);
+
+        let palette_file_input = input::Input::default()
+            .with_label("Palette file: ")
+            .with_size(185, 25)
+            .with_pos(110, 70);
+
+        let mut browse_btn = button::Button::default()
+            .with_label("...")
+            .with_size(30, 25)
+            .with_pos(300, 70);
+
+        browse_btn.set_callback({
+            let mut palette_file_input = palette_file_input.clone();
+            let mut palette_choice = palette_choice.clone();
+            move |_| {
+                let mut dlg = dialog::FileDialog::new(dialog::FileDialogType::BrowseFile);
+                dlg.set_filter("Palettes\t*.{gpl,pal,act,hex,txt}");
+                dlg.show();
+
+                let filename = dlg.filename();
+                if !filename.to_string_lossy().is_empty() {
+                    palette_file_input.set_value(&filename.to_string_lossy());
+                    palette_choice.set_value(2);
+                }
+            }
+        });
+
+        let mut dithering_choice = menu::Choice::default()
+            .with_label("Dithering: ")
+            .with_size(220, 25)
+            .with_pos(110, 100);
+        dithering_choice.add_choice("None|Floyd-Steinberg|Ordered");
+        dithering_choice.set_value(0);
+
+        let mut transparent_input = input::IntInput::default()
+            .with_label("Transparent: ")
+            .with_size(75, 25)
+            .with_pos(110, 130);
+        transparent_input.set_tooltip("Palette index to mark as transparent, empty for none");
+
+        let mut ok_btn = button::Button::default()
+            .with_label("&OK")
+            .with_size(75, 25)
+            .with_pos(340, 10);
+
+        ok_btn.set_callback({
+            let mut window = window.clone();
+            let modal_result = modal_result.clone();
+            move |_| {
+                *modal_result.borrow_mut() = ModalResult::Ok;
+                window.hide();
+            }
+        });
+
+        let mut cancel_btn = button::Button::default()
+            .with_label("&Cancel")
+            .with_size(75, 25)
+            .with_pos(340, 40);
+
+        cancel_btn.set_callback({
+            let mut window = window.clone();
+            move |_| {
+                window.hide();
+            }
+        });
+
+        window.end();
+
+        window.make_modal(true);
+
+        let mut dialog = Self {
+            window,
+            palette_choice,
+            colors_input,
+            palette_file_input,
+            dithering_choice,
+            transparent_input,
+            modal_result,
+        };
+        dialog.colors_input.set_value(&DEFAULT_COLORS.to_string());
+        dialog
+    }
+
+    pub fn show(&mut self) -> Option<IndexedOptions> {
+        *self.modal_result.borrow_mut() = ModalResult::Cancel;
+
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+        }
+
+        match *self.modal_result.borrow() {
+            ModalResult::Ok => Some(self.get_inputs()),
+            ModalResult::Cancel => None,
+        }
+    }
+
+    fn get_inputs(&self) -> IndexedOptions {
+        let palette = match self.palette_choice.value() {
+            1 => PaletteSource::Quantized(
+                self.colors_input
+                    .value()
+                    .parse::<usize>()
+                    .unwrap_or(DEFAULT_COLORS as usize),
+            ),
+            2 => PaletteSource::File(self.palette_file_input.value().into()),
+            _ => PaletteSource::Exact,
+        };
+
+        let dithering = match self.dithering_choice.value() {
+            1 => Dithering::FloydSteinberg,
+            2 => Dithering::Ordered,
+            _ => Dithering::None,
+        };
+
+        let transparent_index = self.transparent_input.value().parse::<u8>().ok();
+
+        IndexedOptions {
+            palette,
+            dithering,
+            transparent_index,
+        }
+    }
+}
@@ -1,6 +1,16 @@
 mod attributes_dialog;
+mod brush_tip;
+mod brush_tips_dialog;
+mod frames_dialog;
+mod indexed_export;
+mod indexed_export_dialog;
+mod multi_frame;
+mod new_document_dialog;
 mod paint_canvas;
 mod res;
+mod selection;
+mod tool_options_bar;
+mod tools;
 
 use fltk::{prelude::*, *};
 use paint_canvas::Canvas;
@@ -10,6 +20,7 @@ const WIDTH: i32 = 800;
 const HEIGHT: i32 = 600;
 const MENUBAR_SIZE: i32 = 30;
 const STATUSBAR_SIZE: i32 = 25;
+const TOOLBAR_SIZE: i32 = 30;
 
 const DEFAULT_CANVAS_WIDTH: i32 = 640;
 const DEFAULT_CANVAS_HEIGHT: i32 = 480;
@@ -24,10 +35,67 @@ fn save_image(canvas: &Canvas, filename: &std::path::Path) {
     }
 }
 
-fn save_image_as(canvas: &Canvas) -> std::path::PathBuf {
+fn open_image(
+    canvas: &mut Canvas,
+    frames_dialog: &mut frames_dialog::FramesDialog,
+) -> Option<std::path::PathBuf> {
+    let mut dlg = dialog::FileDialog::new(dialog::FileDialogType::BrowseFile);
+
+    dlg.set_title("Open");
+    dlg.set_filter("Images\t*.{bmp,jpg,jpeg,gif,tif,tiff,png}");
+
+    dlg.show();
+
+    let filename = dlg.filename();
+    if filename.to_string_lossy().is_empty() {
+        return None;
+    }
+
+    // Multi-page and animated files import the frames picked by the user.
+    // Frames keep 8 bits per channel, so 16-bit pages may open as a single image instead
+    let import_frames = match multi_frame::count_frames(&filename) {
+        Ok(frames) if frames.count > 1 && frames.deep => {
+            match dialog::choice2_default(
+                "Frames are edited with 8 bits per channel.\n\
+                 Import the frames with less precision, \
+                 or open the first page alone with full precision?",
+                "Cancel",
+                "Import Frames",
+                "First Page",
+            ) {
+                Some(1) => true,
+                Some(2) => false,
+                _ => return None,
+            }
+        }
+        Ok(frames) => frames.count > 1,
+        Err(_) => false,
+    };
+    if import_frames {
+        if let Ok(frames) = multi_frame::load_frames(&filename) {
+            let selected = frames_dialog.show(&frames)?;
+            let frames = selected.into_iter().map(|i| frames[i].clone()).collect();
+            canvas.load_frames(frames);
+            return Some(filename);
+        }
+    }
+
+    if canvas.load_image(&filename) {
+        Some(filename)
+    } else {
+        dialog::alert_default(&format!(
+            "Cannot load image from file {}",
+            filename.display()
+        ));
+        None
+    }
+}
+
+fn choose_save_filename(title: &str) -> Option<std::path::PathBuf> {
     const DEFAULT_FILENAME: &str = "untitled.bmp";
     let mut dlg = dialog::FileDialog::new(dialog::FileDialogType::BrowseSaveFile);
 
+    dlg.set_title(title);
     dlg.set_option(dialog::FileDialogOptions::SaveAsConfirm);
     dlg.set_filter("Bitmap\t*.bmp\nJPEG\t*.{jpg,jpeg}\nGIF\t*.gif\nTIFF\t*.{tif,tiff}\nPNG\t*.png");
     dlg.set_preset_file(DEFAULT_FILENAME);
@@ -35,19 +103,85 @@ fn save_image_as(canvas: &Canvas) -> std::path::PathBuf {
     dlg.show();
 
     let filename = dlg.filename();
-    if !filename.to_string_lossy().to_string().is_empty() {
-        save_image(&canvas, &filename)
+    if !filename.to_string_lossy().is_empty() {
+        Some(filename)
     } else {
-        eprintln!("Unable to save an image, file name is empty");
+        None
+    }
+}
+
+fn save_image_as(canvas: &Canvas) -> std::path::PathBuf {
+    match choose_save_filename("Save As") {
+        Some(filename) => {
+            save_image(canvas, &filename);
+            filename
+        }
+        None => {
+            eprintln!("Unable to save an image, file name is empty");
+            std::path::PathBuf::new()
+        }
     }
+}
 
-    filename
+/// Writes the canvas to a new file without making it the current document
+fn export_copy(canvas: &Canvas) {
+    match choose_save_filename("Export Copy") {
+        Some(filename) => save_image(canvas, &filename),
+        None => eprintln!("Unable to export an image, file name is empty"),
+    }
+}
+
+/// Asks for palette settings and writes the canvas as a paletted PNG or GIF
+fn export_indexed(canvas: &Canvas, dialog: &mut indexed_export_dialog::IndexedExportDialog) {
+    let options = match dialog.show() {
+        Some(options) => options,
+        None => return,
+    };
+
+    let mut dlg = dialog::FileDialog::new(dialog::FileDialogType::BrowseSaveFile);
+    dlg.set_title("Export Indexed");
+    dlg.set_option(dialog::FileDialogOptions::SaveAsConfirm);
+    dlg.set_filter("PNG\t*.png\nGIF\t*.gif");
+    dlg.set_preset_file("untitled.png");
+    dlg.show();
+
+    let filename = dlg.filename();
+    if filename.to_string_lossy().is_empty() {
+        eprintln!("Unable to export an image, file name is empty");
+        return;
+    }
+
+    if !canvas.export_indexed(&filename, &options) {
+        dialog::alert_default(&format!(
+            "Cannot export indexed image to file {}",
+            filename.display()
+        ));
+    }
+}
+
+fn frame_status_label(canvas: &Canvas) -> String {
+    if canvas.frame_count() > 1 {
+        format!(
+            "Frame {}/{}",
+            canvas.current_frame() + 1,
+            canvas.frame_count()
+        )
+    } else {
+        "".to_string()
+    }
 }
 
 fn main() {
-    let app = app::App::default().with_scheme(app::Scheme::Gtk);
+    // System fonts are listed by the text tool
+    let app = app::App::default()
+        .with_scheme(app::Scheme::Gtk)
+        .load_system_fonts();
 
     let mut set_size_dialog = attributes_dialog::AttributesDialog::new();
+    let mut indexed_export_dialog = indexed_export_dialog::IndexedExportDialog::new();
+    let mut new_document_dialog = new_document_dialog::NewDocumentDialog::new();
+    let mut frames_dialog = frames_dialog::FramesDialog::new();
+    let mut brush_tips_dialog = brush_tips_dialog::BrushTipsDialog::new();
 
     let mut current_filename = std::path::PathBuf::new();
 
@@ -58,13 +192,31 @@ fn main() {
     #[derive(Copy, Clone)]
     pub enum Message {
         New,
+        Open,
         Save,
         SaveAs,
+        ExportCopy,
+        ExportIndexed,
         Quit,
+        Cut,
+        Copy,
+        Paste,
+        DeleteSelection,
+        SelectAll,
+        Deselect,
         SetImageSize,
         ClearImage,
+        PreviousFrame,
+        NextFrame,
+        DeleteFrame,
         SetFgColor,
         SetBgColor,
+        SelectTool(usize),
+        ToolSizeChanged,
+        ToolOptionChanged(usize, f64),
+        IncreaseToolSize,
+        DecreaseToolSize,
+        BrushTips,
         About,
     }
 
@@ -82,6 +234,13 @@ fn main() {
         tx,
         Message::New,
     );
+    menubar.add_emit(
+        "&File/Open...\t",
+        enums::Shortcut::Ctrl | 'o',
+        menu::MenuFlag::Normal,
+        tx,
+        Message::Open,
+    );
     menubar.add_emit(
         "&File/Save\t",
         enums::Shortcut::Ctrl | 's',
@@ -92,10 +251,24 @@ fn main() {
     menubar.add_emit(
         "&File/Save As...\t",
         enums::Shortcut::Ctrl | enums::Shortcut::Shift | 's',
-        menu::MenuFlag::MenuDivider,
+        menu::MenuFlag::Normal,
         tx,
         Message::SaveAs,
     );
+    menubar.add_emit(
+        "&File/Export Copy...\t",
+        enums::Shortcut::Ctrl | enums::Shortcut::Shift | 'e',
+        menu::MenuFlag::Normal,
+        tx,
+        Message::ExportCopy,
+    );
+    menubar.add_emit(
+        "&File/Export Indexed...\t",
+        enums::Shortcut::None,
+        menu::MenuFlag::MenuDivider,
+        tx,
+        Message::ExportIndexed,
+    );
     menubar.add_emit(
         "&File/Quit\t",
         enums::Shortcut::Ctrl | 'q',
@@ -103,6 +276,48 @@ fn main() {
         tx,
         Message::Quit,
     );
+    menubar.add_emit(
+        "&Edit/Cut\t",
+        enums::Shortcut::Ctrl | 'x',
+        menu::MenuFlag::Normal,
+        tx,
+        Message::Cut,
+    );
+    menubar.add_emit(
+        "&Edit/Copy\t",
+        enums::Shortcut::Ctrl | 'c',
+        menu::MenuFlag::Normal,
+        tx,
+        Message::Copy,
+    );
+    menubar.add_emit(
+        "&Edit/Paste\t",
+        enums::Shortcut::Ctrl | 'v',
+        menu::MenuFlag::Normal,
+        tx,
+        Message::Paste,
+    );
+    menubar.add_emit(
+        "&Edit/Delete\t",
+        enums::Shortcut::None | enums::Key::Delete,
+        menu::MenuFlag::MenuDivider,
+        tx,
+        Message::DeleteSelection,
+    );
+    menubar.add_emit(
+        "&Edit/Select All\t",
+        enums::Shortcut::Ctrl | 'a',
+        menu::MenuFlag::Normal,
+        tx,
+        Message::SelectAll,
+    );
+    menubar.add_emit(
+        "&Edit/Deselect\t",
+        enums::Shortcut::Ctrl | 'd',
+        menu::MenuFlag::Normal,
+        tx,
+        Message::Deselect,
+    );
     menubar.add_emit(
         "&Image/Attributes\t",
         enums::Shortcut::Ctrl | 'e',
@@ -117,6 +332,48 @@ fn main() {
         tx,
         Message::ClearImage,
     );
+    menubar.add_emit(
+        "&Frame/Previous Frame\t",
+        enums::Shortcut::None | enums::Key::PageUp,
+        menu::MenuFlag::Normal,
+        tx,
+        Message::PreviousFrame,
+    );
+    menubar.add_emit(
+        "&Frame/Next Frame\t",
+        enums::Shortcut::None | enums::Key::PageDown,
+        menu::MenuFlag::MenuDivider,
+        tx,
+        Message::NextFrame,
+    );
+    menubar.add_emit(
+        "&Frame/Delete Frame\t",
+        enums::Shortcut::None,
+        menu::MenuFlag::Normal,
+        tx,
+        Message::DeleteFrame,
+    );
+    menubar.add_emit(
+        "&Tools/Increase Size\t",
+        enums::Shortcut::None | ']',
+        menu::MenuFlag::Normal,
+        tx,
+        Message::IncreaseToolSize,
+    );
+    menubar.add_emit(
+        "&Tools/Decrease Size\t",
+        enums::Shortcut::None | '[',
+        menu::MenuFlag::MenuDivider,
+        tx,
+        Message::DecreaseToolSize,
+    );
+    menubar.add_emit(
+        "&Tools/Brush Tips...\t",
+        enums::Shortcut::None,
+        menu::MenuFlag::Normal,
+        tx,
+        Message::BrushTips,
+    );
     menubar.add_emit(
         "&Colors/Foreground...\t",
         enums::Shortcut::None,
@@ -165,6 +422,32 @@ fn main() {
 
     main_layout.fixed(&mut menubar, MENUBAR_SIZE);
 
+    // Toolbar with settings of the current tool
+    let mut tool_size_spinner: misc::Spinner;
+    let mut tool_options_bar: tool_options_bar::ToolOptionsBar;
+    {
+        let mut toolbar = group::Flex::default_fill().row();
+        toolbar.set_margin(2);
+        toolbar.set_frame(enums::FrameType::ThinUpBox);
+
+        let size_label = frame::Frame::default()
+            .with_label("Size:")
+            .with_align(enums::Align::Right | enums::Align::Inside);
+        toolbar.fixed(&size_label, 40);
+
+        tool_size_spinner = misc::Spinner::default();
+        tool_size_spinner.set_range(tools::MIN_TOOL_SIZE as f64, tools::MAX_TOOL_SIZE as f64);
+        tool_size_spinner.set_step(1.0);
+        tool_size_spinner.set_tooltip("Tool size in pixels, also [ and ] or Ctrl+Wheel");
+        tool_size_spinner.emit(tx, Message::ToolSizeChanged);
+        toolbar.fixed(&tool_size_spinner, 70);
+
+        tool_options_bar = tool_options_bar::ToolOptionsBar::new();
+
+        toolbar.end();
+        main_layout.fixed(&toolbar, TOOLBAR_SIZE);
+    }
+
     // Drawing canvas
     let mut canvas_frame: group::Scroll;
     let mut canvas: Canvas;
@@ -186,8 +469,31 @@ fn main() {
         canvas_frame.end();
     }
 
+    // Tool items go before the size items of the Tools menu
+    let tool_names = canvas.tool_names();
+    let first_index = menubar.find_index("&Tools/Increase Size\t");
+    for (i, (name, shortcut)) in tool_names.iter().enumerate() {
+        let mut flag = if i == 0 {
+            menu::MenuFlag::Radio | menu::MenuFlag::Value
+        } else {
+            menu::MenuFlag::Radio
+        };
+        if i == tool_names.len() - 1 {
+            flag |= menu::MenuFlag::MenuDivider;
+        }
+        menubar.insert_emit(
+            first_index + i as i32,
+            &format!("&Tools/{}\t", name),
+            *shortcut,
+            flag,
+            tx,
+            Message::SelectTool(i),
+        );
+    }
+
     // Statusbar
     let mut filename_status: frame::Frame;
+    let mut frame_status: frame::Frame;
     let mut current_coord_status: frame::Frame;
     {
         let mut status_bar = group::Flex::default_fill().row();
@@ -198,6 +504,12 @@ fn main() {
         filename_status.set_align(enums::Align::Left | enums::Align::Inside);
         filename_status.set_frame(enums::FrameType::DownBox);
 
+        // Current frame of a multi-frame document
+        frame_status = frame::Frame::default();
+        frame_status.set_align(enums::Align::Left | enums::Align::Inside);
+        frame_status.set_frame(enums::FrameType::DownBox);
+        status_bar.fixed(&frame_status, 100);
+
         // Current coordinate
         current_coord_status = frame::Frame::default();
         current_coord_status.set_align(enums::Align::Left | enums::Align::Inside);
@@ -215,6 +527,27 @@ fn main() {
     wind.end();
     wind.show();
 
+    canvas.tool_size_change({
+        let mut tool_size_spinner = tool_size_spinner.clone();
+        move |size| match size {
+            Some(size) => {
+                tool_size_spinner.activate();
+                tool_size_spinner.set_value(size as f64);
+            }
+            None => tool_size_spinner.deactivate(),
+        }
+    });
+    if let Some(size) = canvas.get_tool_size() {
+        tool_size_spinner.set_value(size as f64);
+    }
+
+    let show_tool_options = move |bar: &mut tool_options_bar::ToolOptionsBar, canvas: &Canvas| {
+        bar.set_options(&canvas.get_tool_options(), move |index, value| {
+            tx.send(Message::ToolOptionChanged(index, value))
+        });
+    };
+    show_tool_options(&mut tool_options_bar, &canvas);
+
     canvas.coord_change({
         move |c| {
             let str = match c {
@@ -231,11 +564,23 @@ fn main() {
         if let Some(msg) = rx.recv() {
             match msg {
                 Message::New => {
-                    canvas.clean_canvas();
-                    canvas.redraw();
+                    let current_size = canvas.get_size();
+                    if let Some((size, background)) = new_document_dialog.show(current_size) {
+                        canvas.new_document(size, background);
+                        canvas_frame.redraw();
+                        frame_status.set_label(&frame_status_label(&canvas));
 
-                    current_filename = std::path::PathBuf::new();
-                    filename_status.set_label(&current_filename.to_string_lossy().to_string());
+                        current_filename = std::path::PathBuf::new();
+                        filename_status.set_label(&current_filename.to_string_lossy());
+                    }
+                }
+                Message::Open => {
+                    if let Some(filename) = open_image(&mut canvas, &mut frames_dialog) {
+                        current_filename = filename;
+                        filename_status.set_label(&current_filename.to_string_lossy());
+                        frame_status.set_label(&frame_status_label(&canvas));
+                        canvas_frame.redraw();
+                    }
                 }
                 Message::Save => {
                     // Save to current file name or save to new file if current file name is empty
@@ -251,9 +596,34 @@ fn main() {
                     current_filename = save_image_as(&canvas);
                     filename_status.set_label(&current_filename.to_string_lossy().to_string());
                 }
+                Message::ExportCopy => {
+                    // Save to new file but keep working on the current one
+                    export_copy(&canvas);
+                }
+                Message::ExportIndexed => {
+                    export_indexed(&canvas, &mut indexed_export_dialog);
+                }
                 Message::Quit => {
                     app.quit();
                 }
+                Message::Cut => {
+                    canvas.cut_selection();
+                }
+                Message::Copy => {
+                    canvas.copy_selection();
+                }
+                Message::Paste => {
+                    canvas.paste();
+                }
+                Message::DeleteSelection => {
+                    canvas.delete_selection();
+                }
+                Message::SelectAll => {
+                    canvas.select_all();
+                }
+                Message::Deselect => {
+                    canvas.deselect();
+                }
                 Message::SetImageSize => {
                     let current_size = canvas.get_size();
                     if let Some(new_size) = set_size_dialog.show(current_size) {
@@ -264,6 +634,24 @@ fn main() {
                 Message::ClearImage => {
                     canvas.clean_canvas();
                     canvas_frame.redraw();
+                    frame_status.set_label(&frame_status_label(&canvas));
+                }
+                Message::PreviousFrame => {
+                    if canvas.current_frame() > 0 {
+                        canvas.select_frame(canvas.current_frame() - 1);
+                        canvas_frame.redraw();
+                        frame_status.set_label(&frame_status_label(&canvas));
+                    }
+                }
+                Message::NextFrame => {
+                    canvas.select_frame(canvas.current_frame() + 1);
+                    canvas_frame.redraw();
+                    frame_status.set_label(&frame_status_label(&canvas));
+                }
+                Message::DeleteFrame => {
+                    canvas.delete_frame();
+                    canvas_frame.redraw();
+                    frame_status.set_label(&frame_status_label(&canvas));
                 }
                 Message::SetFgColor => {
                     let current_fg_color = canvas.get_fg_color();
@@ -283,6 +671,27 @@ fn main() {
                     );
                     canvas.set_bg_color(bg_color);
                 }
+                Message::SelectTool(index) => {
+                    canvas.set_tool(index);
+                    show_tool_options(&mut tool_options_bar, &canvas);
+                }
+                Message::ToolSizeChanged => {
+                    canvas.set_tool_size(tool_size_spinner.value() as i32);
+                }
+                Message::ToolOptionChanged(index, value) => {
+                    canvas.set_tool_option(index, value);
+                }
+                Message::IncreaseToolSize => {
+                    canvas.step_tool_size(true);
+                }
+                Message::DecreaseToolSize => {
+                    canvas.step_tool_size(false);
+                }
+                Message::BrushTips => {
+                    if let Some(tip) = brush_tips_dialog.show() {
+                        canvas.set_brush_tip(&tip);
+                    }
+                }
                 Message::About => {
                     fltk::app::lock().unwrap();
 
@@ -0,0 +1,224 @@
+use ::image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use ::image::codecs::png::PngDecoder;
+use ::image::{AnimationDecoder, Delay, Frame, RgbaImage};
+
+use crate::paint_canvas::{file_extension, to_display_channel};
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// One page of a multi-page TIFF or one frame of an animation
+#[derive(Clone)]
+pub struct ImageFrame {
+    pub image: RgbaImage,
+    /// Display time of an animation frame, zero for pages
+    pub delay_ms: u32,
+}
+
+/// Frames of a file, found from its headers without decoding any pixels
+pub struct FrameCount {
+    pub count: usize,
+    /// Whether the frames have 16 bits per channel, of which imported frames keep 8
+    pub deep: bool,
+}
+
+/// Counts the frames of an animated GIF or PNG and the pages of a TIFF file.
+/// Other formats and still images have one frame
+/// # Errors
+/// Errors on failure to read file headers
+pub fn count_frames(path: &Path) -> Result<FrameCount, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let reader = BufReader::new(file);
+
+    match file_extension(path).as_str() {
+        "gif" => {
+            // Compressed frame data is skipped over, not decoded
+            let mut options = gif::DecodeOptions::new();
+            options.skip_frame_decoding(true);
+            let mut decoder = options.read_info(reader).map_err(|e| e.to_string())?;
+            let mut count = 0;
+            while decoder
+                .read_next_frame()
+                .map_err(|e| e.to_string())?
+                .is_some()
+            {
+                count += 1;
+            }
+            Ok(FrameCount { count, deep: false })
+        }
+        "png" => {
+            let decoder = png::Decoder::new(reader);
+            let reader = decoder.read_info().map_err(|e| e.to_string())?;
+            let info = reader.info();
+            Ok(FrameCount {
+                count: info
+                    .animation_control
+                    .map_or(1, |control| control.num_frames as usize),
+                deep: info.bit_depth == png::BitDepth::Sixteen,
+            })
+        }
+        "tif" | "tiff" => {
+            use tiff::decoder::Decoder;
+            use tiff::ColorType;
+
+            let mut decoder = Decoder::new(reader).map_err(|e| e.to_string())?;
+            let mut frames = FrameCount {
+                count: 1,
+                deep: false,
+            };
+            loop {
+                let color = decoder.colortype().map_err(|e| e.to_string())?;
+                frames.deep |= matches!(
+                    color,
+                    ColorType::Gray(16)
+                        | ColorType::GrayA(16)
+                        | ColorType::RGB(16)
+                        | ColorType::RGBA(16)
+                );
+                if !decoder.more_images() {
+                    break;
+                }
+                decoder.next_image().map_err(|e| e.to_string())?;
+                frames.count += 1;
+            }
+            Ok(frames)
+        }
+        _ => Ok(FrameCount {
+            count: 1,
+            deep: false,
+        }),
+    }
+}
+
+/// Reads every frame of an animated GIF or PNG and every page of a TIFF file.
+/// Other formats give an empty list and should be opened as a single image
+/// # Errors
+/// Errors on failure to read or decode file
+pub fn load_frames(path: &Path) -> Result<Vec<ImageFrame>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let reader = BufReader::new(file);
+
+    match file_extension(path).as_str() {
+        "gif" => {
+            let decoder = GifDecoder::new(reader).map_err(|e| e.to_string())?;
+            collect_animation(decoder.into_frames())
+        }
+        "png" => {
+            let decoder = PngDecoder::new(reader).map_err(|e| e.to_string())?;
+            if decoder.is_apng() {
+                collect_animation(decoder.apng().into_frames())
+            } else {
+                Ok(Vec::new())
+            }
+        }
+        "tif" | "tiff" => load_tiff_pages(reader),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn collect_animation(frames: ::image::Frames) -> Result<Vec<ImageFrame>, String> {
+    let frames = frames.collect_frames().map_err(|e| e.to_string())?;
+    Ok(frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            ImageFrame {
+                delay_ms: numer / denom.max(1),
+                image: frame.into_buffer(),
+            }
+        })
+        .collect())
+}
+
+fn load_tiff_pages(reader: BufReader<File>) -> Result<Vec<ImageFrame>, String> {
+    use tiff::decoder::{Decoder, DecodingResult};
+    use tiff::ColorType;
+
+    let mut decoder = Decoder::new(reader).map_err(|e| e.to_string())?;
+    let mut pages = Vec::new();
+
+    loop {
+        let (w, h) = decoder.dimensions().map_err(|e| e.to_string())?;
+        let color = decoder.colortype().map_err(|e| e.to_string())?;
+        let data = decoder.read_image().map_err(|e| e.to_string())?;
+
+        // Pages are edited with 8 bits per channel
+        let samples = match data {
+            DecodingResult::U8(data) => data,
+            DecodingResult::U16(data) => data.iter().map(|v| to_display_channel(*v)).collect(),
+            _ => return Err("Unsupported TIFF sample format".to_string()),
+        };
+        let rgba = match color {
+            ColorType::Gray(8 | 16) => samples.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+            ColorType::GrayA(8 | 16) => samples
+                .chunks_exact(2)
+                .flat_map(|c| [c[0], c[0], c[0], c[1]])
+                .collect(),
+            ColorType::RGB(8 | 16) => samples
+                .chunks_exact(3)
+                .flat_map(|c| [c[0], c[1], c[2], 255])
+                .collect(),
+            ColorType::RGBA(8 | 16) => samples,
+            _ => return Err(format!("Unsupported TIFF color type {:?}", color)),
+        };
+
+        let image = RgbaImage::from_raw(w, h, rgba)
+            .ok_or_else(|| "TIFF page has unexpected size".to_string())?;
+        pages.push(ImageFrame { image, delay_ms: 0 });
+
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image().map_err(|e| e.to_string())?;
+    }
+
+    Ok(pages)
+}
+
+/// Whether all frames of a document can be written into the file
+pub fn is_multi_frame_format(path: &Path) -> bool {
+    matches!(file_extension(path).as_str(), "gif" | "tif" | "tiff")
+}
+
+/// Writes frames as an animated GIF or as a multi-page TIFF
+/// # Errors
+/// Errors on failure to encode or write file
+pub fn save_frames(path: &Path, frames: &[ImageFrame]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+
+    match file_extension(path).as_str() {
+        "gif" => {
+            let mut encoder = GifEncoder::new(BufWriter::new(file));
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(|e| e.to_string())?;
+            encoder
+                .encode_frames(frames.iter().map(|frame| {
+                    Frame::from_parts(
+                        frame.image.clone(),
+                        0,
+                        0,
+                        Delay::from_numer_denom_ms(frame.delay_ms, 1),
+                    )
+                }))
+                .map_err(|e| e.to_string())
+        }
+        "tif" | "tiff" => {
+            use tiff::encoder::{colortype, TiffEncoder};
+
+            let mut encoder = TiffEncoder::new(BufWriter::new(file)).map_err(|e| e.to_string())?;
+            for frame in frames {
+                encoder
+                    .write_image::<colortype::RGBA8>(
+                        frame.image.width(),
+                        frame.image.height(),
+                        frame.image.as_raw(),
+                    )
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+        _ => Err("Only GIF and TIFF files can hold several frames".to_string()),
+    }
+}
@@ -0,0 +1,406 @@
+use fltk::{prelude::*, *};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::paint_canvas::Background;
+
+#[derive(Copy, Clone)]
+enum ModalResult {
+    Ok,
+    Cancel,
+}
+
+const DEFAULT_DPI: f64 = 96.0;
+const MM_PER_INCH: f64 = 25.4;
+const PRESETS_FILENAME: &str = "presets.txt";
+/// Longest side of a new document, larger surfaces take too much memory
+const MAX_SIDE: i32 = 10000;
+
+/// Whether a document of this size can be created
+fn is_valid_size(w: i32, h: i32) -> bool {
+    (1..=MAX_SIDE).contains(&w) && (1..=MAX_SIDE).contains(&h)
+}
+
+#[derive(Clone)]
+enum PresetSize {
+    Pixels(i32, i32),
+    /// Paper size, converted to pixels with the DPI from the dialog
+    Millimeters(f64, f64),
+}
+
+#[derive(Clone)]
+struct Preset {
+    name: String,
+    size: PresetSize,
+}
+
+impl Preset {
+    fn pixels(name: &str, w: i32, h: i32) -> Self {
+        Self {
+            name: name.to_string(),
+            size: PresetSize::Pixels(w, h),
+        }
+    }
+
+    fn paper(name: &str, w: f64, h: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            size: PresetSize::Millimeters(w, h),
+        }
+    }
+
+    fn size_at(&self, dpi: f64) -> (i32, i32) {
+        match self.size {
+            PresetSize::Pixels(w, h) => (w, h),
+            PresetSize::Millimeters(w, h) => (
+                (w / MM_PER_INCH * dpi).round() as i32,
+                (h / MM_PER_INCH * dpi).round() as i32,
+            ),
+        }
+    }
+}
+
+fn builtin_presets() -> Vec<Preset> {
+    vec![
+        Preset::pixels("Screen 640 x 480", 640, 480),
+        Preset::pixels("Screen 800 x 600", 800, 600),
+        Preset::pixels("Screen 1024 x 768", 1024, 768),
+        Preset::pixels("Screen 1280 x 720", 1280, 720),
+        Preset::pixels("Screen 1366 x 768", 1366, 768),
+        Preset::pixels("Screen 1920 x 1080", 1920, 1080),
+        Preset::pixels("Screen 2560 x 1440", 2560, 1440),
+        Preset::pixels("Screen 3840 x 2160", 3840, 2160),
+        Preset::paper("Paper A3", 297.0, 420.0),
+        Preset::paper("Paper A4", 210.0, 297.0),
+        Preset::paper("Paper A5", 148.0, 210.0),
+        Preset::paper("Paper Letter", 215.9, 279.4),
+        Preset::paper("Paper Legal", 215.9, 355.6),
+        Preset::pixels("Icon 16 x 16", 16, 16),
+        Preset::pixels("Icon 32 x 32", 32, 32),
+        Preset::pixels("Icon 48 x 48", 48, 48),
+        Preset::pixels("Icon 64 x 64", 64, 64),
+        Preset::pixels("Icon 128 x 128", 128, 128),
+        Preset::pixels("Icon 256 x 256", 256, 256),
+        Preset::pixels("Icon 512 x 512", 512, 512),
+    ]
+}
+
+/// Location of the file with presets saved by the user
+fn user_presets_path() -> Option<std::path::PathBuf> {
+    let config_dir = std::env::var_os("APPDATA")
+        .or_else(|| std::env::var_os("XDG_CONFIG_HOME"))
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })?;
+    Some(config_dir.join("rusty_painter").join(PRESETS_FILENAME))
+}
+
+/// Reads user presets, one `width height name` entry per line
+fn load_user_presets() -> Vec<Preset> {
+    let Some(path) = user_presets_path() else {
+        return Vec::new();
+    };
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            let w = parts.next()?.parse::<i32>().ok()?;
+            let h = parts.next()?.parse::<i32>().ok()?;
+            let name = parts.next()?.trim();
+            Some(Preset::pixels(&format!("User {}", name), w, h))
+        })
+        .collect()
+}
+
+fn save_user_preset(name: &str, size: (i32, i32)) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let path =
+        user_presets_path().ok_or_else(|| std::io::Error::other("No configuration directory"))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{} {} {}", size.0, size.1, name)
+}
+
+pub struct NewDocumentDialog {
+    window: window::Window,
+    width_input: input::IntInput,
+    height_input: input::IntInput,
+    orientation_choice: menu::Choice,
+    background_choice: menu::Choice,
+    modal_result: Rc<RefCell<ModalResult>>,
+}
+
+impl NewDocumentDialog {
+    pub fn new() -> Self {
+        let modal_result = Rc::from(RefCell::from(ModalResult::Cancel));
+        let presets = Rc::from(RefCell::from(Vec::<Preset>::new()));
+
+        let mut window = window::Window::default()
+            .with_label("New Document")
+            .with_size(400, 165);
+
+        let mut preset_choice = menu::Choice::default()
+            .with_label("Preset: ")
+            .with_size(200, 25)
+            .with_pos(100, 10);
+
+        let mut width_input = input::IntInput::default()
+            .with_label("Width: ")
+            .with_size(75, 25)
+            .with_pos(100, 40);
+
+        let mut height_input = input::IntInput::default()
+            .with_label("Height: ")
+            .with_size(75, 25)
+            .with_pos(225, 40);
+
+        let mut dpi_input = input::IntInput::default()
+            .with_label("DPI: ")
+            .with_size(75, 25)
+            .with_pos(100, 70);
+        dpi_input.set_value(&DEFAULT_DPI.to_string());
+        dpi_input.set_tooltip("Resolution used to convert paper sizes to pixels");
+
+        let mut orientation_choice = menu::Choice::default()
+            .with_label("Orientation: ")
+            .with_size(200, 25)
+            .with_pos(100, 100);
+        orientation_choice.add_choice("Portrait|Landscape");
+        orientation_choice.set_value(1);
+
+        let mut background_choice = menu::Choice::default()
+            .with_label("Background: ")
+            .with_size(200, 25)
+            .with_pos(100, 130);
+        background_choice.add_choice("White|Background color|Transparent");
+        background_choice.set_value(0);
+
+        let mut fill_presets = {
+            let presets = presets.clone();
+            let mut preset_choice = preset_choice.clone();
+            move || {
+                let mut presets = presets.borrow_mut();
+                *presets = builtin_presets();
+                presets.extend(load_user_presets());
+
+                preset_choice.clear();
+                preset_choice.add_choice("Custom");
+                for preset in presets.iter() {
+                    // Slashes and bars have a special meaning in menu labels
+                    preset_choice.add_choice(&preset.name.replace(['/', '|'], "-"));
+                }
+                preset_choice.set_value(0);
+            }
+        };
+        fill_presets();
+
+        // Orientation swaps the inputs so that the longer side is vertical or horizontal
+        let apply_orientation = {
+            let mut width_input = width_input.clone();
+            let mut height_input = height_input.clone();
+            let orientation_choice = orientation_choice.clone();
+            move || {
+                let w = width_input.value().parse::<i32>().unwrap_or(0);
+                let h = height_input.value().parse::<i32>().unwrap_or(0);
+                let landscape = orientation_choice.value() == 1;
+                if (landscape && w < h) || (!landscape && w > h) {
+                    width_input.set_value(&h.to_string());
+                    height_input.set_value(&w.to_string());
+                }
+            }
+        };
+
+        let apply_preset = {
+            let presets = presets.clone();
+            let preset_choice = preset_choice.clone();
+            let mut width_input = width_input.clone();
+            let mut height_input = height_input.clone();
+            let dpi_input = dpi_input.clone();
+            let mut apply_orientation = apply_orientation.clone();
+            move || {
+                let index = preset_choice.value();
+                if index < 1 {
+                    return;
+                }
+                if let Some(preset) = presets.borrow().get(index as usize - 1) {
+                    let dpi = dpi_input.value().parse::<f64>().unwrap_or(DEFAULT_DPI);
+                    let size = preset.size_at(dpi);
+                    width_input.set_value(&size.0.to_string());
+                    height_input.set_value(&size.1.to_string());
+                }
+                apply_orientation();
+            }
+        };
+
+        preset_choice.set_callback({
+            let presets = presets.clone();
+            let mut orientation_choice = orientation_choice.clone();
+            let mut apply_preset = apply_preset.clone();
+            move |c| {
+                // Start from the natural orientation of the preset
+                let preset = usize::try_from(c.value() - 1)
+                    .ok()
+                    .and_then(|index| presets.borrow().get(index).cloned());
+                if let Some(preset) = preset {
+                    let (w, h) = preset.size_at(DEFAULT_DPI);
+                    if w != h {
+                        orientation_choice.set_value(if w > h { 1 } else { 0 });
+                    }
+                }
+                apply_preset()
+            }
+        });
+        dpi_input.set_trigger(enums::CallbackTrigger::Changed);
+        dpi_input.set_callback({
+            let mut apply_preset = apply_preset.clone();
+            move |_| apply_preset()
+        });
+        orientation_choice.set_callback({
+            let mut apply_orientation = apply_orientation.clone();
+            move |_| apply_orientation()
+        });
+
+        // Editing the size by hand leaves the preset
+        for input in [&mut width_input, &mut height_input] {
+            input.set_trigger(enums::CallbackTrigger::Changed);
+            input.set_callback({
+                let mut preset_choice = preset_choice.clone();
+                move |_| {
+                    preset_choice.set_value(0);
+                }
+            });
+        }
+
+        let mut ok_btn = button::Button::default()
+            .with_label("&OK")
+            .with_size(75, 25)
+            .with_pos(315, 10);
+
+        ok_btn.set_callback({
+            let mut window = window.clone();
+            let width_input = width_input.clone();
+            let height_input = height_input.clone();
+            let modal_result = modal_result.clone();
+            move |_| {
+                let w = width_input.value().parse::<i32>().unwrap_or(0);
+                let h = height_input.value().parse::<i32>().unwrap_or(0);
+                if !is_valid_size(w, h) {
+                    dialog::alert_default(&format!(
+                        "Width and height must be from 1 to {} pixels",
+                        MAX_SIDE
+                    ));
+                    return;
+                }
+
+                *modal_result.borrow_mut() = ModalResult::Ok;
+                window.hide();
+            }
+        });
+
+        let mut cancel_btn = button::Button::default()
+            .with_label("&Cancel")
+            .with_size(75, 25)
+            .with_pos(315, 40);
+
+        cancel_btn.set_callback({
+            let mut window = window.clone();
+            move |_| {
+                window.hide();
+            }
+        });
+
+        let mut save_preset_btn = button::Button::default()
+            .with_label("&Save Preset")
+            .with_size(75, 25)
+            .with_pos(315, 130);
+
+        save_preset_btn.set_callback({
+            let width_input = width_input.clone();
+            let height_input = height_input.clone();
+            let mut preset_choice = preset_choice.clone();
+            let mut fill_presets = fill_presets.clone();
+            move |_| {
+                let w = width_input.value().parse::<i32>().unwrap_or(0);
+                let h = height_input.value().parse::<i32>().unwrap_or(0);
+                if !is_valid_size(w, h) {
+                    return;
+                }
+
+                let default_name = format!("{} x {}", w, h);
+                if let Some(name) = dialog::input_default("Preset name:", &default_name) {
+                    let name = name.trim();
+                    if name.is_empty() {
+                        return;
+                    }
+                    if let Err(error) = save_user_preset(name, (w, h)) {
+                        dialog::alert_default(&format!("Cannot save preset {}: {}", name, error));
+                        return;
+                    }
+
+                    fill_presets();
+                    // Select the new preset, it is the last one
+                    preset_choice.set_value(preset_choice.size() - 2);
+                }
+            }
+        });
+
+        window.end();
+
+        window.make_modal(true);
+
+        Self {
+            window,
+            width_input,
+            height_input,
+            orientation_choice,
+            background_choice,
+            modal_result,
+        }
+    }
+
+    pub fn show(&mut self, size: (i32, i32)) -> Option<((i32, i32), Background)> {
+        *self.modal_result.borrow_mut() = ModalResult::Cancel;
+        self.width_input.set_value(size.0.to_string().as_str());
+        self.height_input.set_value(size.1.to_string().as_str());
+        self.orientation_choice
+            .set_value(if size.0 < size.1 { 0 } else { 1 });
+
+        self.window.show();
+        while self.window.shown() {
+            app::wait();
+        }
+
+        match *self.modal_result.borrow() {
+            ModalResult::Ok => self.get_inputs(),
+            ModalResult::Cancel => None,
+        }
+    }
+
+    fn get_inputs(&self) -> Option<((i32, i32), Background)> {
+        let w = self.width_input.value().parse::<i32>().ok()?;
+        let h = self.height_input.value().parse::<i32>().ok()?;
+        if !is_valid_size(w, h) {
+            return None;
+        }
+
+        let background = match self.background_choice.value() {
+            1 => Background::BgColor,
+            2 => Background::Transparent,
+            _ => Background::White,
+        };
+
+        Some(((w, h), background))
+    }
+}
@@ -1,103 +1,511 @@
-use ::image::RgbImage;
+use ::image::{
+    ColorType, DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, LumaA, Rgb, RgbImage,
+    Rgba,
+};
 
+use crate::brush_tip::BrushTip;
+use crate::indexed_export::{self, IndexedOptions};
+use crate::multi_frame::{self, ImageFrame};
+use crate::selection::{PixelBlock, Selection};
+use crate::tools::{self, CoordOption, Tool, ToolContext, ToolOption};
 use fltk::{prelude::*, *};
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-type CoordOption = Option<draw::Coord<i32>>;
+pub type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+pub type Gray16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+/// Seconds between steps of the marching ants animation
+const ANTS_INTERVAL: f64 = 0.2;
+
+/// Converts a 16-bit channel value into the 8-bit value shown on screen
+pub fn to_display_channel(v: u16) -> u8 {
+    (v >> 8) as u8
+}
+
+/// Widens an 8-bit channel value so that it keeps its brightness in 16 bits
+pub fn to_deep_channel(v: u8) -> u16 {
+    v as u16 * 257
+}
+
+/// Pixels of a document opened from a 16-bit file
+pub struct DeepImage {
+    pub pixels: Rgb16Image,
+    /// Full precision opacity of a file with an alpha channel. Tools edit the alpha
+    /// surface, its values that still match these are saved from here
+    alpha: Option<Gray16Image>,
+    /// Pixels brought down to 8 bits, kept up to date with every write
+    pub display: RgbImage,
+}
+
+impl DeepImage {
+    fn new(pixels: Rgb16Image, alpha: Option<Gray16Image>) -> Self {
+        let display = RgbImage::from_fn(pixels.width(), pixels.height(), |x, y| {
+            Rgb(pixels.get_pixel(x, y).0.map(to_display_channel))
+        });
+        Self {
+            pixels,
+            alpha,
+            display,
+        }
+    }
+
+    /// Full precision pixels of an image with 16 bits per channel, `None` for 8-bit images
+    fn from_image(img: &DynamicImage) -> Option<Self> {
+        match img.color() {
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+                let alpha = img.color().has_alpha().then(|| {
+                    let rgba = img.to_rgba16();
+                    Gray16Image::from_fn(rgba.width(), rgba.height(), |x, y| {
+                        Luma([rgba.get_pixel(x, y).0[3]])
+                    })
+                });
+                Some(Self::new(img.to_rgb16(), alpha))
+            }
+            _ => None,
+        }
+    }
+
+    /// Sets a pixel together with the color it shows
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Rgb<u16>) {
+        self.pixels.put_pixel(x, y, color);
+        self.display
+            .put_pixel(x, y, Rgb(color.0.map(to_display_channel)));
+    }
+
+    /// Fills the whole image with one color, the opacity kept from the file goes away
+    fn fill(&mut self, color: Rgb<u16>) {
+        let shown = Rgb(color.0.map(to_display_channel));
+        self.pixels.pixels_mut().for_each(|p| *p = color);
+        self.display.pixels_mut().for_each(|p| *p = shown);
+        self.alpha = None;
+    }
+
+    /// 16-bit opacity of the document, `alpha` holds the 8 bits that tools left
+    fn full_alpha(&self, alpha: &GrayImage) -> Gray16Image {
+        Gray16Image::from_fn(alpha.width(), alpha.height(), |x, y| {
+            let Luma([a]) = *alpha.get_pixel(x, y);
+            match &self.alpha {
+                Some(deep) if to_display_channel(deep.get_pixel(x, y).0[0]) == a => {
+                    *deep.get_pixel(x, y)
+                }
+                _ => Luma([to_deep_channel(a)]),
+            }
+        })
+    }
+}
+
+/// Saves a 16-bit document. Documents opened from grayscale files are saved
+/// as grayscale again, unless color has been painted on them
+fn save_deep_image(
+    path: &std::path::Path,
+    deep: &DeepImage,
+    alpha: Option<&GrayImage>,
+    grayscale: bool,
+) -> ::image::ImageResult<()> {
+    let pixels = &deep.pixels;
+    let (w, h) = pixels.dimensions();
+    let grayscale = grayscale
+        && pixels
+            .pixels()
+            .all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]);
+    let alpha = alpha.map(|alpha| deep.full_alpha(alpha));
+    match (grayscale, alpha) {
+        (true, None) => {
+            ImageBuffer::from_fn(w, h, |x, y| Luma([pixels.get_pixel(x, y).0[0]])).save(path)
+        }
+        // TIFF has no 16-bit gray with alpha
+        (true, Some(alpha)) if file_extension(path) == "png" => {
+            ImageBuffer::from_fn(w, h, |x, y| {
+                LumaA([pixels.get_pixel(x, y).0[0], alpha.get_pixel(x, y).0[0]])
+            })
+            .save(path)
+        }
+        (_, Some(alpha)) => ImageBuffer::from_fn(w, h, |x, y| {
+            let c = pixels.get_pixel(x, y).0;
+            Rgba([c[0], c[1], c[2], alpha.get_pixel(x, y).0[0]])
+        })
+        .save(path),
+        (false, None) => pixels.save(path),
+    }
+}
+
+pub(crate) fn file_extension(path: &std::path::Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn is_deep_format(path: &std::path::Path) -> bool {
+    matches!(file_extension(path).as_str(), "png" | "tif" | "tiff")
+}
+
+fn is_alpha_format(path: &std::path::Path) -> bool {
+    matches!(
+        file_extension(path).as_str(),
+        "png" | "tif" | "tiff" | "gif" | "bmp"
+    )
+}
+
+/// Background of a new document
+#[derive(Copy, Clone, PartialEq)]
+pub enum Background {
+    White,
+    BgColor,
+    Transparent,
+}
+
+/// Draws pixels at the top left corner of a surface
+pub fn draw_surface_data(
+    surf: &surface::ImageSurface,
+    data: &[u8],
+    size: (i32, i32),
+    depth: enums::ColorDepth,
+) {
+    surface::ImageSurface::push_current(surf);
+    let _ = draw::draw_image(data, 0, 0, size.0, size.1, depth);
+    surface::ImageSurface::pop_current();
+}
+
+fn fill_surface(surf: &surface::ImageSurface, size: (i32, i32), color: enums::Color) {
+    surface::ImageSurface::push_current(surf);
+    draw::draw_rect_fill(0, 0, size.0, size.1, color);
+    surface::ImageSurface::pop_current();
+}
+
+/// Reads opacity of a transparent document from its alpha mask
+pub fn capture_alpha(alpha_surf: &surface::ImageSurface, size: (i32, i32)) -> Option<GrayImage> {
+    let img = draw::capture_surface(alpha_surf, size.0, size.1).ok()?;
+    let data = img.to_rgb_data();
+    GrayImage::from_raw(
+        size.0 as u32,
+        size.1 as u32,
+        data.chunks_exact(3).map(|c| c[0]).collect(),
+    )
+}
+
+/// Draws grey and white squares that show through transparent parts of a document
+fn draw_checkerboard(x: i32, y: i32, w: i32, h: i32) {
+    const CELL_SIZE: i32 = 8;
+    draw::draw_rect_fill(x, y, w, h, enums::Color::White);
+    for cy in (0..h).step_by(CELL_SIZE as usize) {
+        for cx in (0..w).step_by(CELL_SIZE as usize) {
+            if (cx / CELL_SIZE + cy / CELL_SIZE) % 2 == 1 {
+                draw::draw_rect_fill(
+                    x + cx,
+                    y + cy,
+                    CELL_SIZE.min(w - cx),
+                    CELL_SIZE.min(h - cy),
+                    enums::Color::Light2,
+                );
+            }
+        }
+    }
+}
 
 struct CanvasInternal {
     size: (i32, i32),
     fg_color: enums::Color,
     bg_color: enums::Color,
-    instrument_size: i32,
+    tools: Vec<Box<dyn Tool>>,
+    current_tool: usize,
+    /// Index of the eyedropper that picks colors while Alt is held
+    eyedropper_tool: Option<usize>,
+    /// Set from a press with Alt until release, mouse events go to the eyedropper meanwhile
+    picking_color: bool,
+    /// Timer of a tool that works over time while a mouse button is held
+    repeat_timeout: Option<app::TimeoutHandle>,
+    selection: Rc<RefCell<Option<Selection>>>,
+    /// Step of the marching ants animation around the selection
+    ants_phase: i32,
+    /// Pixels of the last copy or cut
+    clipboard: Option<PixelBlock>,
     coord: CoordOption,
     coord_change_cb: Box<dyn FnMut(CoordOption)>,
+    tool_size_change_cb: Box<dyn FnMut(Option<i32>)>,
+    /// Pixels of a document opened from a 16-bit file, shared with tools.
+    /// They replace the surface, which then goes unused
+    deep_image: Rc<RefCell<Option<DeepImage>>>,
+    /// Whether the 16-bit document was opened from a grayscale file
+    grayscale: bool,
+    /// Frames of a multi-frame document, empty for a single image.
+    /// Pixels of the current frame live on the surfaces and are stored back on frame switch
+    frames: Vec<ImageFrame>,
+    current_frame: usize,
 }
 
 impl CanvasInternal {
     fn new(w: i32, h: i32) -> Self {
-        Self {
+        let tools = tools::all_tools();
+        let eyedropper_tool = tools
+            .iter()
+            .position(|tool| tool.name() == tools::Eyedropper::NAME);
+        let brush_size = tools
+            .iter()
+            .find(|tool| tool.uses_brush_size())
+            .and_then(|tool| tool.size());
+
+        let mut canvas_internal = Self {
             size: (w, h),
             fg_color: enums::Color::Red,
             bg_color: enums::Color::White,
-            instrument_size: 5,
+            tools,
+            current_tool: 0,
+            eyedropper_tool,
+            picking_color: false,
+            repeat_timeout: None,
+            selection: Rc::from(RefCell::from(None)),
+            ants_phase: 0,
+            clipboard: None,
             coord: None,
             coord_change_cb: Box::new(|_| {}),
+            tool_size_change_cb: Box::new(|_| {}),
+            deep_image: Rc::from(RefCell::from(None)),
+            grayscale: false,
+            frames: Vec::new(),
+            current_frame: 0,
+        };
+        // Tools that paint with the size of the brush start from the same size
+        if let Some(size) = brush_size {
+            canvas_internal.set_brush_size(size);
+        }
+        canvas_internal
+    }
+    fn tool_context<'a>(
+        &self,
+        surf: &'a surface::ImageSurface,
+        alpha_surf: Option<&'a surface::ImageSurface>,
+    ) -> ToolContext<'a> {
+        ToolContext {
+            surf,
+            alpha_surf,
+            size: self.size,
+            fg_color: self.fg_color,
+            bg_color: self.bg_color,
+            selection: self.selection.clone(),
+            deep_image: self.deep_image.clone(),
+        }
+    }
+    /// Keeps colors picked by a tool
+    fn store_context_colors(&mut self, ctx: &ToolContext) {
+        self.fg_color = ctx.fg_color;
+        self.bg_color = ctx.bg_color;
+    }
+    /// Tool that receives mouse button events
+    fn pointer_tool(&mut self) -> &mut Box<dyn Tool> {
+        match self.eyedropper_tool {
+            Some(index) if self.picking_color => &mut self.tools[index],
+            _ => &mut self.tools[self.current_tool],
         }
     }
-    fn instrument_push(&mut self, coord: draw::Coord<i32>, surf: &surface::ImageSurface) {
-        surface::ImageSurface::push_current(&surf);
+    fn instrument_push(
+        &mut self,
+        coord: draw::Coord<i32>,
+        surf: &surface::ImageSurface,
+        alpha_surf: Option<&surface::ImageSurface>,
+    ) {
+        self.picking_color = app::is_event_alt() && !self.tools[self.current_tool].uses_alt();
 
-        // Draw with current instrument
-        draw::draw_circle_fill(coord.0, coord.1, self.instrument_size, self.fg_color);
+        let mut ctx = self.tool_context(surf, alpha_surf);
+        self.pointer_tool().push(&mut ctx, coord);
+        self.store_context_colors(&ctx);
 
         self.coord = Some(coord);
         (self.coord_change_cb.as_mut())(self.coord);
-
-        surface::ImageSurface::pop_current();
     }
-    fn instrument_drag(&mut self, coord_new: draw::Coord<i32>, surf: &surface::ImageSurface) {
-        surface::ImageSurface::push_current(&surf);
-
-        if let Some(c) = self.coord {
-            draw::set_draw_color(self.fg_color);
-            draw::set_line_style(
-                draw::LineStyle::Solid | draw::LineStyle::CapRound,
-                self.instrument_size,
-            );
-            draw::draw_line(c.0, c.1, coord_new.0, coord_new.1);
-
-            self.coord = Some(coord_new);
-            (self.coord_change_cb.as_mut())(self.coord);
-        }
+    fn instrument_drag(
+        &mut self,
+        coord: draw::Coord<i32>,
+        surf: &surface::ImageSurface,
+        alpha_surf: Option<&surface::ImageSurface>,
+    ) {
+        let mut ctx = self.tool_context(surf, alpha_surf);
+        self.pointer_tool().drag(&mut ctx, coord);
+        self.store_context_colors(&ctx);
 
-        surface::ImageSurface::pop_current();
+        self.coord = Some(coord);
+        (self.coord_change_cb.as_mut())(self.coord);
+    }
+    fn instrument_released(
+        &mut self,
+        coord: draw::Coord<i32>,
+        surf: &surface::ImageSurface,
+        alpha_surf: Option<&surface::ImageSurface>,
+    ) {
+        let mut ctx = self.tool_context(surf, alpha_surf);
+        self.pointer_tool().released(&mut ctx, coord);
+        self.store_context_colors(&ctx);
+        self.picking_color = false;
     }
-    fn instrument_released(&mut self, _coord: draw::Coord<i32>, _surf: &surface::ImageSurface) {
-        //
+    fn instrument_repeat(
+        &mut self,
+        surf: &surface::ImageSurface,
+        alpha_surf: Option<&surface::ImageSurface>,
+    ) {
+        let mut ctx = self.tool_context(surf, alpha_surf);
+        self.pointer_tool().repeat(&mut ctx);
+        self.store_context_colors(&ctx);
     }
-    fn instrument_move(&mut self, coord: draw::Coord<i32>, _surf: &surface::ImageSurface) {
+    fn instrument_move(
+        &mut self,
+        coord: draw::Coord<i32>,
+        surf: &surface::ImageSurface,
+        alpha_surf: Option<&surface::ImageSurface>,
+    ) {
+        let mut ctx = self.tool_context(surf, alpha_surf);
+        self.tools[self.current_tool].moved(&mut ctx, coord);
+        self.store_context_colors(&ctx);
+
+        draw::set_cursor(self.tools[self.current_tool].cursor());
+
         self.coord = Some(coord);
         (self.coord_change_cb.as_mut())(self.coord);
     }
+    fn instrument_key(
+        &mut self,
+        key: enums::Key,
+        surf: &surface::ImageSurface,
+        alpha_surf: Option<&surface::ImageSurface>,
+    ) -> bool {
+        let mut ctx = self.tool_context(surf, alpha_surf);
+        let handled = self.tools[self.current_tool].key(&mut ctx, key);
+        self.store_context_colors(&ctx);
+        handled
+    }
+    fn instrument_finish(
+        &mut self,
+        surf: &surface::ImageSurface,
+        alpha_surf: Option<&surface::ImageSurface>,
+    ) {
+        let mut ctx = self.tool_context(surf, alpha_surf);
+        self.tools[self.current_tool].finish(&mut ctx);
+    }
     fn instrument_enter(&mut self, coord: draw::Coord<i32>) {
+        draw::set_cursor(self.tools[self.current_tool].cursor());
+
         self.coord = Some(coord);
         (self.coord_change_cb.as_mut())(self.coord);
     }
     fn instrument_leave(&mut self, _coord: draw::Coord<i32>) {
+        draw::set_cursor(enums::Cursor::Default);
+
         self.coord = None;
         (self.coord_change_cb.as_mut())(self.coord);
     }
-    fn draw(&self, x: i32, y: i32, w: i32, h: i32, surf: &surface::ImageSurface) {
+    fn draw(
+        &self,
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+        surf: &surface::ImageSurface,
+        alpha_surf: Option<&surface::ImageSurface>,
+    ) {
         draw::push_clip(x, y, w, h);
 
-        let mut img = surf.image().unwrap();
-        img.draw(x, y, self.size.0, self.size.1);
+        // 16-bit documents show the 8-bit copy of their pixels
+        let deep_image = self.deep_image.borrow();
+        let display = deep_image.as_ref().map(|deep| &deep.display);
+
+        match alpha_surf {
+            Some(alpha_surf) => {
+                // Blend transparent document over a checkerboard
+                draw_checkerboard(x, y, self.size.0, self.size.1);
 
-        if let Some(c) = self.coord {
-            let instrument_color = enums::Color::contrast(self.fg_color, self.bg_color);
-            draw::set_draw_color(instrument_color);
-            draw::set_line_style(draw::LineStyle::Solid, 1);
-            draw::draw_circle(
-                (x + c.0) as f64,
-                (y + c.1) as f64,
-                (self.instrument_size as f64) / 2.0,
-            );
+                let data = match display {
+                    Some(display) => Some(Cow::Borrowed(display.as_raw().as_slice())),
+                    None => draw::capture_surface(surf, self.size.0, self.size.1)
+                        .ok()
+                        .map(|img| Cow::Owned(img.to_rgb_data())),
+                };
+                let alpha = capture_alpha(alpha_surf, self.size);
+                if let (Some(data), Some(alpha)) = (data, alpha) {
+                    let data = data
+                        .chunks_exact(3)
+                        .zip(alpha.iter())
+                        .flat_map(|(c, a)| [c[0], c[1], c[2], *a])
+                        .collect::<Vec<u8>>();
+                    if let Ok(mut img) = fltk::image::RgbImage::new(
+                        &data,
+                        self.size.0,
+                        self.size.1,
+                        enums::ColorDepth::Rgba8,
+                    ) {
+                        img.draw(x, y, self.size.0, self.size.1);
+                    }
+                }
+            }
+            None => match display {
+                Some(display) => {
+                    if let Ok(mut img) = fltk::image::RgbImage::new(
+                        display.as_raw(),
+                        self.size.0,
+                        self.size.1,
+                        enums::ColorDepth::Rgb8,
+                    ) {
+                        img.draw(x, y, self.size.0, self.size.1);
+                    }
+                }
+                None => {
+                    let mut img = surf.image().unwrap();
+                    img.draw(x, y, self.size.0, self.size.1);
+                }
+            },
+        }
+        drop(deep_image);
+
+        let ctx = self.tool_context(surf, alpha_surf);
+        self.tools[self.current_tool].draw_overlay(&ctx, (x, y), self.coord);
+
+        if let Some(selection) = self.selection.borrow().as_ref() {
+            selection.draw_outline((x, y), self.ants_phase);
         }
 
         draw::pop_clip();
     }
-    fn clean(&mut self, surf: &surface::ImageSurface) {
-        surface::ImageSurface::push_current(&surf);
-
-        draw::draw_rect_fill(0, 0, self.size.0, self.size.1, self.bg_color);
+    fn clean(&mut self, surf: &surface::ImageSurface, alpha_surf: Option<&surface::ImageSurface>) {
+        fill_surface(surf, self.size, self.bg_color);
+        if let Some(deep) = self.deep_image.borrow_mut().as_mut() {
+            let (r, g, b) = self.bg_color.to_rgb();
+            deep.fill(Rgb([r, g, b].map(to_deep_channel)));
+        }
 
-        surface::ImageSurface::pop_current();
+        // Transparent documents are cleared to full transparency
+        if let Some(alpha_surf) = alpha_surf {
+            fill_surface(alpha_surf, self.size, enums::Color::Black);
+        }
     }
     fn coord_change<F: FnMut(CoordOption) + 'static>(&mut self, cb: F) {
         self.coord_change_cb = Box::new(cb);
     }
+    fn tool_size_change<F: FnMut(Option<i32>) + 'static>(&mut self, cb: F) {
+        self.tool_size_change_cb = Box::new(cb);
+    }
+    fn get_tool_size(&self) -> Option<i32> {
+        self.tools[self.current_tool].size()
+    }
+    fn set_tool_size(&mut self, size: i32) {
+        let size = size.clamp(tools::MIN_TOOL_SIZE, tools::MAX_TOOL_SIZE);
+        if self.tools[self.current_tool].uses_brush_size() {
+            self.set_brush_size(size);
+        } else {
+            self.tools[self.current_tool].set_size(size);
+        }
+        let size = self.get_tool_size();
+        (self.tool_size_change_cb.as_mut())(size);
+    }
+    /// Gives the size to every tool that paints with the size of the brush
+    fn set_brush_size(&mut self, size: i32) {
+        for tool in self.tools.iter_mut().filter(|tool| tool.uses_brush_size()) {
+            tool.set_size(size);
+        }
+    }
+    fn step_tool_size(&mut self, grow: bool) {
+        if let Some(size) = self.get_tool_size() {
+            self.set_tool_size(tools::step_tool_size(size, grow));
+        }
+    }
     fn get_fg_color(&self) -> (u8, u8, u8) {
         self.fg_color.to_rgb()
     }
@@ -116,6 +524,8 @@ pub struct Canvas {
     frame: frame::Frame,
     #[allow(dead_code)]
     surf: Rc<RefCell<surface::ImageSurface>>,
+    /// Opacity of a transparent document, white is opaque and black is transparent
+    alpha_surf: Rc<RefCell<Option<surface::ImageSurface>>>,
     canvas_internal: Rc<RefCell<CanvasInternal>>,
 }
 
@@ -127,33 +537,91 @@ impl Canvas {
 
         let surf = surface::ImageSurface::new(surf_w, surf_h, false);
         let surf = Rc::from(RefCell::from(surf));
+        let alpha_surf = Rc::from(RefCell::from(None));
 
         let canvas_internal = CanvasInternal::new(surf_w, surf_h);
         let canvas_internal = Rc::from(RefCell::from(canvas_internal));
 
         frame.draw({
             let surf = surf.clone();
+            let alpha_surf = alpha_surf.clone();
             let canvas_internal = canvas_internal.clone();
             move |f| {
                 let surf = surf.borrow();
+                let alpha_surf = alpha_surf.borrow();
                 let canvas_internal = canvas_internal.borrow();
 
-                canvas_internal.draw(f.x(), f.y(), f.w(), f.h(), &surf);
+                canvas_internal.draw(f.x(), f.y(), f.w(), f.h(), &surf, alpha_surf.as_ref());
+            }
+        });
+
+        // Marching ants move while there is a selection
+        app::add_timeout3(ANTS_INTERVAL, {
+            let canvas_internal = canvas_internal.clone();
+            let mut frame = frame.clone();
+            move |handle| {
+                if let Ok(mut canvas_internal) = canvas_internal.try_borrow_mut() {
+                    if canvas_internal.selection.borrow().is_some() {
+                        canvas_internal.ants_phase -= 1;
+                        frame.redraw();
+                    }
+                }
+                app::repeat_timeout3(ANTS_INTERVAL, handle);
             }
         });
 
+        // Calls the tool at its interval until the mouse button is released
+        let start_repeat = {
+            let surf = surf.clone();
+            let alpha_surf = alpha_surf.clone();
+            let canvas_internal = canvas_internal.clone();
+            move |frame: &frame::Frame, interval: f64| {
+                let surf = surf.clone();
+                let alpha_surf = alpha_surf.clone();
+                let canvas_internal = canvas_internal.clone();
+                let mut frame = frame.clone();
+                app::add_timeout3(interval, move |handle| {
+                    let surf = surf.borrow();
+                    let alpha_surf = alpha_surf.borrow();
+                    canvas_internal
+                        .borrow_mut()
+                        .instrument_repeat(&surf, alpha_surf.as_ref());
+
+                    frame.redraw();
+                    app::repeat_timeout3(interval, handle);
+                })
+            }
+        };
+
         frame.handle({
             let surf = surf.clone();
+            let alpha_surf = alpha_surf.clone();
             let canvas_internal = canvas_internal.clone();
             move |f, ev| {
+                // Keyboard focus brings key presses to the current tool
+                if matches!(ev, enums::Event::Focus | enums::Event::Unfocus) {
+                    return true;
+                }
+
                 let surf = surf.borrow_mut();
+                let alpha_surf = alpha_surf.borrow();
+                let alpha_surf = alpha_surf.as_ref();
                 let mut canvas_internal = canvas_internal.borrow_mut();
                 match ev {
                     enums::Event::Push => {
+                        f.take_focus().ok();
+
                         let coords = app::event_coords();
                         let coords = draw::Coord::<i32>(coords.0 - f.x(), coords.1 - f.y());
 
-                        canvas_internal.instrument_push(coords, &surf);
+                        canvas_internal.instrument_push(coords, &surf, alpha_surf);
+
+                        if let Some(interval) = canvas_internal.pointer_tool().repeat_interval() {
+                            if let Some(handle) = canvas_internal.repeat_timeout.take() {
+                                app::remove_timeout3(handle);
+                            }
+                            canvas_internal.repeat_timeout = Some(start_repeat(f, interval));
+                        }
 
                         f.redraw();
                         true
@@ -162,7 +630,7 @@ impl Canvas {
                         let coords = app::event_coords();
                         let coords = draw::Coord::<i32>(coords.0 - f.x(), coords.1 - f.y());
 
-                        canvas_internal.instrument_drag(coords, &surf);
+                        canvas_internal.instrument_drag(coords, &surf, alpha_surf);
 
                         f.redraw();
                         true
@@ -171,7 +639,10 @@ impl Canvas {
                         let coords = app::event_coords();
                         let coords = draw::Coord::<i32>(coords.0 - f.x(), coords.1 - f.y());
 
-                        canvas_internal.instrument_released(coords, &surf);
+                        if let Some(handle) = canvas_internal.repeat_timeout.take() {
+                            app::remove_timeout3(handle);
+                        }
+                        canvas_internal.instrument_released(coords, &surf, alpha_surf);
 
                         f.redraw();
                         true
@@ -180,11 +651,26 @@ impl Canvas {
                         let coords = app::event_coords();
                         let coords = draw::Coord::<i32>(coords.0 - f.x(), coords.1 - f.y());
 
-                        canvas_internal.instrument_move(coords, &surf);
+                        canvas_internal.instrument_move(coords, &surf, alpha_surf);
+
+                        f.redraw();
+                        true
+                    }
+                    enums::Event::MouseWheel if app::is_event_ctrl() => {
+                        // Ctrl+wheel changes size of the tool instead of scrolling
+                        canvas_internal.step_tool_size(app::event_dy_value() < 0);
 
                         f.redraw();
                         true
                     }
+                    enums::Event::KeyDown => {
+                        let handled =
+                            canvas_internal.instrument_key(app::event_key(), &surf, alpha_surf);
+                        if handled {
+                            f.redraw();
+                        }
+                        handled
+                    }
                     enums::Event::Enter => {
                         let coords = app::event_coords();
                         let coords = draw::Coord::<i32>(coords.0 - f.x(), coords.1 - f.y());
@@ -210,12 +696,69 @@ impl Canvas {
         Self {
             frame,
             surf,
+            alpha_surf,
             canvas_internal,
         }
     }
 
     pub fn clean_canvas(&self) {
-        self.canvas_internal.borrow_mut().clean(&self.surf.borrow());
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        canvas_internal.selection.replace(None);
+        canvas_internal.clean(&self.surf.borrow(), self.alpha_surf.borrow().as_ref());
+    }
+
+    /// Replaces the document with an empty one of the given size
+    pub fn new_document(&mut self, size: (i32, i32), background: Background) {
+        let color = match background {
+            Background::White => enums::Color::White,
+            Background::BgColor | Background::Transparent => self.canvas_internal.borrow().bg_color,
+        };
+
+        let surf = surface::ImageSurface::new(size.0, size.1, false);
+        fill_surface(&surf, size, color);
+        self.surf.replace(surf);
+
+        let alpha_surf = if background == Background::Transparent {
+            let alpha_surf = surface::ImageSurface::new(size.0, size.1, false);
+            fill_surface(&alpha_surf, size, enums::Color::Black);
+            Some(alpha_surf)
+        } else {
+            None
+        };
+        self.alpha_surf.replace(alpha_surf);
+
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        canvas_internal.size = size;
+        canvas_internal.deep_image.replace(None);
+        canvas_internal.frames.clear();
+        canvas_internal.current_frame = 0;
+        canvas_internal.selection.replace(None);
+        drop(canvas_internal);
+
+        self.set_size(size.0, size.1);
+        self.frame.set_size(size.0, size.1);
+    }
+
+    /// Replaces surfaces of the document with new ones holding the given pixels
+    fn replace_surfaces(&mut self, size: (i32, i32), rgb: &[u8], alpha: Option<&[u8]>) {
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        canvas_internal.size = size;
+        canvas_internal.selection.replace(None);
+        drop(canvas_internal);
+
+        let surf = surface::ImageSurface::new(size.0, size.1, false);
+        draw_surface_data(&surf, rgb, size, enums::ColorDepth::Rgb8);
+        self.surf.replace(surf);
+
+        let alpha_surf = alpha.map(|alpha| {
+            let alpha_surf = surface::ImageSurface::new(size.0, size.1, false);
+            draw_surface_data(&alpha_surf, alpha, size, enums::ColorDepth::L8);
+            alpha_surf
+        });
+        self.alpha_surf.replace(alpha_surf);
+
+        self.set_size(size.0, size.1);
+        self.frame.set_size(size.0, size.1);
     }
 
     pub fn coord_change<F: FnMut(CoordOption) + 'static>(&mut self, cb: F) {
@@ -236,40 +779,435 @@ impl Canvas {
         self.canvas_internal.borrow_mut().set_bg_color(c);
     }
 
+    pub fn set_tool(&mut self, index: usize) {
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        if index < canvas_internal.tools.len() {
+            canvas_internal
+                .instrument_finish(&self.surf.borrow(), self.alpha_surf.borrow().as_ref());
+            canvas_internal.current_tool = index;
+
+            let size = canvas_internal.get_tool_size();
+            (canvas_internal.tool_size_change_cb.as_mut())(size);
+        }
+        drop(canvas_internal);
+        self.frame.redraw();
+    }
+
+    /// Sets callback called when size of the current tool changes or another tool is selected
+    pub fn tool_size_change<F: FnMut(Option<i32>) + 'static>(&mut self, cb: F) {
+        self.canvas_internal.borrow_mut().tool_size_change(cb);
+    }
+    pub fn get_tool_size(&self) -> Option<i32> {
+        self.canvas_internal.borrow().get_tool_size()
+    }
+    pub fn set_tool_size(&mut self, size: i32) {
+        self.canvas_internal.borrow_mut().set_tool_size(size);
+        self.frame.redraw();
+    }
+    pub fn step_tool_size(&mut self, grow: bool) {
+        self.canvas_internal.borrow_mut().step_tool_size(grow);
+        self.frame.redraw();
+    }
+
+    /// Names and shortcuts of the tools, in the order of set_tool's index
+    pub fn tool_names(&self) -> Vec<(&'static str, enums::Shortcut)> {
+        let canvas_internal = self.canvas_internal.borrow();
+        canvas_internal
+            .tools
+            .iter()
+            .map(|tool| (tool.name(), tool.shortcut()))
+            .collect()
+    }
+
+    /// Settings of the current tool shown in the toolbar
+    pub fn get_tool_options(&self) -> Vec<ToolOption> {
+        let canvas_internal = self.canvas_internal.borrow();
+        canvas_internal.tools[canvas_internal.current_tool].options()
+    }
+    pub fn set_tool_option(&mut self, index: usize, value: f64) {
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        let current_tool = canvas_internal.current_tool;
+        canvas_internal.tools[current_tool].set_option(index, value);
+        drop(canvas_internal);
+        self.frame.redraw();
+    }
+
+    /// Gives the tip to every tool that stamps one, not only the current tool
+    pub fn set_brush_tip(&mut self, tip: &BrushTip) {
+        for tool in self.canvas_internal.borrow_mut().tools.iter_mut() {
+            tool.set_brush_tip(tip);
+        }
+    }
+
+    pub fn select_all(&mut self) {
+        let canvas_internal = self.canvas_internal.borrow();
+        let (w, h) = canvas_internal.size;
+        canvas_internal
+            .selection
+            .replace(Some(Selection::rectangle(0, 0, w, h)));
+        drop(canvas_internal);
+        self.frame.redraw();
+    }
+    pub fn deselect(&mut self) {
+        self.canvas_internal.borrow().selection.replace(None);
+        self.frame.redraw();
+    }
+
+    /// Clears the selected pixels to the background, or to transparency in transparent documents
+    pub fn delete_selection(&mut self) {
+        let canvas_internal = self.canvas_internal.borrow();
+        let bounds = canvas_internal
+            .selection
+            .borrow()
+            .as_ref()
+            .map(|s| s.bounds);
+        if let Some((x, y, w, h)) = bounds {
+            let surf = self.surf.borrow();
+            let alpha_surf = self.alpha_surf.borrow();
+            let ctx = canvas_internal.tool_context(&surf, alpha_surf.as_ref());
+            ctx.erase((x, y, w, h), |color, origin| {
+                draw::draw_rect_fill(x + origin.0, y + origin.1, w, h, color)
+            });
+        }
+        drop(canvas_internal);
+        self.frame.redraw();
+    }
+
+    /// Keeps the selected pixels for pasting
+    pub fn copy_selection(&mut self) {
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        let selection = canvas_internal.selection.borrow().clone();
+        let size = canvas_internal.size;
+        if let Some(selection) = selection.and_then(|s| s.clipped(size)) {
+            let surf = self.surf.borrow();
+            let alpha_surf = self.alpha_surf.borrow();
+            let ctx = canvas_internal.tool_context(&surf, alpha_surf.as_ref());
+            let block = ctx.read_block(selection.bounds).map(|block| PixelBlock {
+                mask: selection.mask,
+                ..block
+            });
+            canvas_internal.clipboard = block;
+        }
+    }
+    pub fn cut_selection(&mut self) {
+        self.copy_selection();
+        self.delete_selection();
+    }
+
+    /// Draws copied pixels at the top left corner of the selection or of the image
+    /// and selects them
+    pub fn paste(&mut self) {
+        let canvas_internal = self.canvas_internal.borrow();
+        if let Some(block) = canvas_internal.clipboard.as_ref() {
+            let (x, y) = canvas_internal
+                .selection
+                .borrow()
+                .as_ref()
+                .map_or((0, 0), |s| (s.bounds.0, s.bounds.1));
+            let (w, h) = (block.image.width() as i32, block.image.height() as i32);
+
+            let surf = self.surf.borrow();
+            let alpha_surf = self.alpha_surf.borrow();
+            let ctx = canvas_internal.tool_context(&surf, alpha_surf.as_ref());
+            ctx.write_block((x, y), block);
+
+            let selection = Selection::new((x, y, w, h), block.mask.clone());
+            canvas_internal
+                .selection
+                .replace(selection.clipped(canvas_internal.size));
+        }
+        drop(canvas_internal);
+        self.frame.redraw();
+    }
+
     pub fn get_size(&self) -> (i32, i32) {
         self.canvas_internal.borrow().size
     }
     pub fn set_image_size(&mut self, size: (i32, i32)) {
+        // 16-bit pixels are cropped or extended on their own, the surface does not hold them
+        let deep_image = self.canvas_internal.borrow().deep_image.take().map(|deep| {
+            let (r, g, b) = self.canvas_internal.borrow().bg_color.to_rgb();
+            let bg = Rgb([r, g, b].map(to_deep_channel));
+            let (w, h) = (size.0 as u32, size.1 as u32);
+            let pixels = Rgb16Image::from_fn(w, h, |x, y| {
+                if deep.pixels.in_bounds(x, y) {
+                    *deep.pixels.get_pixel(x, y)
+                } else {
+                    bg
+                }
+            });
+            // Added area is transparent
+            let alpha = deep.alpha.map(|alpha| {
+                Gray16Image::from_fn(w, h, |x, y| {
+                    if alpha.in_bounds(x, y) {
+                        *alpha.get_pixel(x, y)
+                    } else {
+                        Luma([0])
+                    }
+                })
+            });
+            DeepImage::new(pixels, alpha)
+        });
+
         let old_size = self.canvas_internal.borrow_mut().size;
         self.canvas_internal.borrow_mut().size = size;
+        self.canvas_internal.borrow_mut().selection.replace(None);
 
         let surf = surface::ImageSurface::new(size.0, size.1, false);
         let old_surf = self.surf.replace(surf);
 
-        self.clean_canvas();
+        let old_alpha_surf = self.alpha_surf.borrow_mut().take();
+        if old_alpha_surf.is_some() {
+            let alpha_surf = surface::ImageSurface::new(size.0, size.1, false);
+            self.alpha_surf.replace(Some(alpha_surf));
+        }
+
+        self.canvas_internal
+            .borrow_mut()
+            .clean(&self.surf.borrow(), self.alpha_surf.borrow().as_ref());
 
-        // Draw old surface on top of the new one
-        if let Ok(img) = draw::capture_surface(&old_surf, old_size.0, old_size.1) {
-            surface::ImageSurface::push_current(&self.surf.borrow_mut());
+        // Draw old surfaces on top of the new ones
+        let surfaces = [
+            (Some(old_surf), Some(self.surf.borrow())),
+            (
+                old_alpha_surf,
+                std::cell::Ref::filter_map(self.alpha_surf.borrow(), Option::as_ref).ok(),
+            ),
+        ];
+        for (old_surf, surf) in surfaces {
+            if let (Some(old_surf), Some(surf)) = (old_surf, surf) {
+                if let Ok(img) = draw::capture_surface(&old_surf, old_size.0, old_size.1) {
+                    surface::ImageSurface::push_current(&surf);
 
-            let data = img.to_rgb_data();
+                    let data = img.to_rgb_data();
 
-            let _ = draw::draw_image(
-                &data.to_vec(),
-                0,
-                0,
-                old_size.0,
-                old_size.1,
-                enums::ColorDepth::Rgb8,
-            );
+                    let _ = draw::draw_image(
+                        &data.to_vec(),
+                        0,
+                        0,
+                        old_size.0,
+                        old_size.1,
+                        enums::ColorDepth::Rgb8,
+                    );
 
-            surface::ImageSurface::pop_current();
+                    surface::ImageSurface::pop_current();
+                }
+            }
         }
 
+        self.canvas_internal.borrow().deep_image.replace(deep_image);
+
         self.set_size(size.0, size.1);
         self.frame.set_size(size.0, size.1);
     }
 
+    /// Replaces the document with an image file.
+    /// 16-bit PNG and TIFF files keep their full precision pixels in a buffer
+    /// that tools edit in place of the surface
+    /// # Errors
+    /// Errors on failure to read file
+    pub fn load_image<P: AsRef<std::path::Path>>(&mut self, path: P) -> bool {
+        let path = path.as_ref();
+
+        let img = match ::image::open(path) {
+            Ok(img) => img,
+            Err(error) => {
+                eprintln!(
+                    "Cannot load image from file {}. Error: {}",
+                    path.display(),
+                    error
+                );
+                return false;
+            }
+        };
+
+        let size = (img.width() as i32, img.height() as i32);
+        let deep_image = DeepImage::from_image(&img);
+        // The surface of a 16-bit document goes unused
+        let data = match &deep_image {
+            Some(_) => vec![0; (size.0 * size.1 * 3) as usize],
+            None => img.to_rgb8().into_raw(),
+        };
+
+        // Files with an alpha channel open as transparent documents
+        let alpha = match deep_image.as_ref().and_then(|deep| deep.alpha.as_ref()) {
+            Some(alpha) => Some(alpha.iter().map(|a| to_display_channel(*a)).collect()),
+            None if img.color().has_alpha() => {
+                Some(img.to_rgba8().pixels().map(|p| p.0[3]).collect::<Vec<u8>>())
+            }
+            None => None,
+        };
+
+        self.replace_surfaces(size, &data, alpha.as_deref());
+
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        canvas_internal.deep_image.replace(deep_image);
+        canvas_internal.grayscale = !img.color().has_color();
+        canvas_internal.frames.clear();
+        canvas_internal.current_frame = 0;
+        drop(canvas_internal);
+
+        println!("Loaded image from file {}", path.display());
+        true
+    }
+
+    /// Replaces the document with frames of an animation or pages of a multi-page file.
+    /// Document takes the size of the largest frame
+    pub fn load_frames(&mut self, frames: Vec<ImageFrame>) {
+        let size = frames.iter().fold((1, 1), |size, frame| {
+            (
+                size.0.max(frame.image.width() as i32),
+                size.1.max(frame.image.height() as i32),
+            )
+        });
+        let transparent = frames
+            .iter()
+            .any(|frame| frame.image.pixels().any(|p| p.0[3] < 255));
+
+        let rgb = vec![255u8; (size.0 * size.1 * 3) as usize];
+        let alpha = vec![0u8; (size.0 * size.1) as usize];
+        self.replace_surfaces(size, &rgb, transparent.then_some(alpha.as_slice()));
+
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        canvas_internal.deep_image.replace(None);
+        canvas_internal.frames = frames;
+        canvas_internal.current_frame = 0;
+        drop(canvas_internal);
+
+        self.show_frame(0);
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.canvas_internal.borrow().frames.len().max(1)
+    }
+
+    pub fn current_frame(&self) -> usize {
+        self.canvas_internal.borrow().current_frame
+    }
+
+    /// Makes another frame of a multi-frame document editable
+    pub fn select_frame(&mut self, index: usize) {
+        if index >= self.frame_count() || index == self.current_frame() {
+            return;
+        }
+
+        self.store_current_frame();
+        self.show_frame(index);
+    }
+
+    /// Removes the current frame, the last remaining frame cannot be deleted
+    pub fn delete_frame(&mut self) {
+        if self.frame_count() < 2 {
+            return;
+        }
+
+        let index = {
+            let mut canvas_internal = self.canvas_internal.borrow_mut();
+            let index = canvas_internal.current_frame;
+            canvas_internal.frames.remove(index);
+            index.min(canvas_internal.frames.len() - 1)
+        };
+        self.show_frame(index);
+    }
+
+    /// Copies pixels of the current frame from the surfaces into the frame list
+    fn store_current_frame(&self) {
+        if self.canvas_internal.borrow().frames.is_empty() {
+            return;
+        }
+
+        if let Ok(img) = self.capture_image() {
+            let alpha = self.capture_alpha();
+            let image = ::image::RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+                let c = img.get_pixel(x, y).0;
+                let a = alpha
+                    .as_ref()
+                    .map_or(255, |alpha| alpha.get_pixel(x, y).0[0]);
+                Rgba([c[0], c[1], c[2], a])
+            });
+
+            let mut canvas_internal = self.canvas_internal.borrow_mut();
+            let current_frame = canvas_internal.current_frame;
+            canvas_internal.frames[current_frame].image = image;
+        }
+    }
+
+    /// Draws a stored frame on the surfaces, frames of other sizes are cropped or extended
+    fn show_frame(&self, index: usize) {
+        let mut canvas_internal = self.canvas_internal.borrow_mut();
+        canvas_internal.current_frame = index;
+        canvas_internal.clean(&self.surf.borrow(), self.alpha_surf.borrow().as_ref());
+
+        let image = &canvas_internal.frames[index].image;
+        let size = (image.width() as i32, image.height() as i32);
+        let rgb = image
+            .pixels()
+            .flat_map(|p| [p.0[0], p.0[1], p.0[2]])
+            .collect::<Vec<u8>>();
+        draw_surface_data(&self.surf.borrow(), &rgb, size, enums::ColorDepth::Rgb8);
+
+        if let Some(alpha_surf) = self.alpha_surf.borrow().as_ref() {
+            let alpha = image.pixels().map(|p| p.0[3]).collect::<Vec<u8>>();
+            draw_surface_data(alpha_surf, &alpha, size, enums::ColorDepth::L8);
+        }
+    }
+
+    /// All frames of the document brought to the current document size
+    fn document_frames(&self) -> Vec<ImageFrame> {
+        self.store_current_frame();
+
+        let canvas_internal = self.canvas_internal.borrow();
+        let (w, h) = (canvas_internal.size.0 as u32, canvas_internal.size.1 as u32);
+        let bg = canvas_internal.bg_color.to_rgb();
+        let fill = if self.alpha_surf.borrow().is_some() {
+            Rgba([bg.0, bg.1, bg.2, 0])
+        } else {
+            Rgba([bg.0, bg.1, bg.2, 255])
+        };
+
+        canvas_internal
+            .frames
+            .iter()
+            .map(|frame| ImageFrame {
+                image: ::image::RgbaImage::from_fn(w, h, |x, y| {
+                    if frame.image.in_bounds(x, y) {
+                        *frame.image.get_pixel(x, y)
+                    } else {
+                        fill
+                    }
+                }),
+                delay_ms: frame.delay_ms,
+            })
+            .collect()
+    }
+
+    /// Captures contents of the canvas as an RGB image
+    /// # Errors
+    /// Errors when the surface cannot be read back
+    pub fn capture_image(&self) -> Result<RgbImage, FltkError> {
+        let canvas_internal = self.canvas_internal.borrow();
+        if let Some(deep) = canvas_internal.deep_image.borrow().as_ref() {
+            return Ok(deep.display.clone());
+        }
+
+        let img =
+            draw::capture_surface(&self.surf.borrow(), self.frame.width(), self.frame.height())?;
+        let data = img.to_rgb_data();
+
+        Ok(RgbImage::from_raw(
+            self.frame.width() as u32,
+            self.frame.height() as u32,
+            data.to_vec(),
+        )
+        .expect("container should have the right size for the image dimensions"))
+    }
+
+    /// Captures opacity of a transparent document
+    pub fn capture_alpha(&self) -> Option<GrayImage> {
+        let alpha_surf = self.alpha_surf.borrow();
+        capture_alpha(alpha_surf.as_ref()?, self.get_size())
+    }
+
     /// Saves a canvas into an image file
     /// # Errors
     /// Errors on failure to save file
@@ -278,31 +1216,56 @@ impl Canvas {
 
         let path = path.as_ref().to_str().unwrap();
 
-        let result =
-            draw::capture_surface(&self.surf.borrow(), self.frame.width(), self.frame.height());
+        // Animated GIF and multi-page TIFF keep all frames, other formats get the current one
+        if self.frame_count() > 1 && multi_frame::is_multi_frame_format(path.as_ref()) {
+            return match multi_frame::save_frames(path.as_ref(), &self.document_frames()) {
+                Ok(()) => {
+                    println!("Save image to file {}", path);
+                    true
+                }
+                Err(error) => {
+                    eprintln!("Cannot save image to file {}. Error: {}", path, error);
+                    false
+                }
+            };
+        }
+
+        let alpha = if is_alpha_format(path.as_ref()) {
+            self.capture_alpha()
+        } else {
+            None
+        };
 
-        match result {
-            Ok(img) => {
-                let data = img.to_rgb_data();
-
-                let img = RgbImage::from_raw(
-                    self.frame.width() as u32,
-                    self.frame.height() as u32,
-                    data.to_vec(),
-                )
-                .expect("container should have the right size for the image dimensions");
-
-                let result = img.save(path);
-                match result {
-                    Ok(()) => {
-                        println!("Save image to file {}", path);
-                        true
+        let canvas_internal = self.canvas_internal.borrow();
+        let deep_image = canvas_internal.deep_image.borrow();
+        let result = match deep_image.as_ref() {
+            Some(deep) if is_deep_format(path.as_ref()) => save_deep_image(
+                path.as_ref(),
+                deep,
+                alpha.as_ref(),
+                canvas_internal.grayscale,
+            )
+            .map_err(|e| e.to_string()),
+            _ => self
+                .capture_image()
+                .map_err(|e| e.to_string())
+                .and_then(|img| {
+                    match alpha {
+                        Some(alpha) => ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+                            let c = img.get_pixel(x, y).0;
+                            let Luma([a]) = *alpha.get_pixel(x, y);
+                            Rgba([c[0], c[1], c[2], a])
+                        })
+                        .save(path),
+                        None => img.save(path),
                     }
-                    Err(error) => {
-                        eprintln!("Cannot save image to file {}. Error: {}", path, error);
-                        false
-                    }
-                }
+                    .map_err(|e| e.to_string())
+                }),
+        };
+        match result {
+            Ok(()) => {
+                println!("Save image to file {}", path);
+                true
             }
             Err(error) => {
                 eprintln!("Cannot save image to file {}. Error: {}", path, error);
@@ -310,6 +1273,110 @@ impl Canvas {
             }
         }
     }
+
+    /// Saves a canvas into a paletted PNG or GIF file
+    /// # Errors
+    /// Errors on failure to build the palette or to save file
+    pub fn export_indexed<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        options: &IndexedOptions,
+    ) -> bool {
+        let path = path.as_ref();
+
+        let result = self
+            .capture_image()
+            .map_err(|e| e.to_string())
+            .and_then(|img| {
+                indexed_export::save_indexed(&img, self.capture_alpha().as_ref(), path, options)
+            });
+        match result {
+            Ok(()) => {
+                println!("Exported indexed image to file {}", path.display());
+                true
+            }
+            Err(error) => {
+                eprintln!(
+                    "Cannot export indexed image to file {}. Error: {}",
+                    path.display(),
+                    error
+                );
+                false
+            }
+        }
+    }
 }
 
 fltk::widget_extends!(Canvas, frame::Frame, frame);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_png(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rusty_painter_{}_{}.png", name, std::process::id()))
+    }
+
+    #[test]
+    fn edit_survives_16_bit_png_round_trip() {
+        let path = temp_png("round_trip");
+        ImageBuffer::<Luma<u16>, Vec<u16>>::from_pixel(4, 4, Luma([0x80ab]))
+            .save(&path)
+            .unwrap();
+
+        let img = ::image::open(&path).unwrap();
+        let mut deep = DeepImage::from_image(&img).unwrap();
+
+        // 8-bit tools write the pixels they leave unchanged as well
+        let unmasked = PixelBlock {
+            image: RgbImage::from_fn(2, 1, |x, _| match x {
+                0 => Rgb([0x80, 0x80, 0x80]),
+                _ => Rgb([0x40, 0x40, 0x40]),
+            }),
+            alpha: None,
+            mask: None,
+        };
+        tools::write_deep_block(&mut deep, (1, 2), &unmasked);
+
+        // Paint that keeps the high byte of the pixel still has to reach the file
+        let masked = PixelBlock {
+            image: RgbImage::from_pixel(1, 1, Rgb([0x80, 0x80, 0x80])),
+            alpha: None,
+            mask: Some(GrayImage::from_pixel(1, 1, Luma([255]))),
+        };
+        tools::write_deep_block(&mut deep, (0, 0), &masked);
+        save_deep_image(&path, &deep, None, !img.color().has_color()).unwrap();
+
+        let saved = ::image::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.color(), ColorType::L16);
+        let saved = saved.into_luma16();
+        assert_eq!(saved.get_pixel(1, 2).0[0], 0x80ab);
+        assert_eq!(saved.get_pixel(2, 2).0[0], 0x4040);
+        assert_eq!(saved.get_pixel(0, 0).0[0], 0x8080);
+        assert_eq!(saved.get_pixel(3, 3).0[0], 0x80ab);
+    }
+
+    #[test]
+    fn opacity_survives_16_bit_png_round_trip() {
+        let path = temp_png("opacity");
+        ImageBuffer::<LumaA<u16>, Vec<u16>>::from_pixel(2, 1, LumaA([0x1234, 0x80ab]))
+            .save(&path)
+            .unwrap();
+
+        let img = ::image::open(&path).unwrap();
+        let deep = DeepImage::from_image(&img).unwrap();
+
+        // Opacity as the alpha surface holds it, with one pixel erased halfway
+        let mut alpha = GrayImage::from_pixel(2, 1, Luma([to_display_channel(0x80ab)]));
+        alpha.put_pixel(1, 0, Luma([0x40]));
+        save_deep_image(&path, &deep, Some(&alpha), !img.color().has_color()).unwrap();
+
+        let saved = ::image::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.color(), ColorType::La16);
+        let saved = saved.into_luma_alpha16();
+        assert_eq!(saved.get_pixel(0, 0).0, [0x1234, 0x80ab]);
+        assert_eq!(saved.get_pixel(1, 0).0, [0x1234, 0x4040]);
+    }
+}
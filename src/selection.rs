@@ -0,0 +1,317 @@
+use ::image::{imageops, GrayImage, Luma, RgbImage};
+use fltk::{prelude::*, *};
+use std::cell::OnceCell;
+
+/// Length of one dash of the selection outline
+const ANT_LENGTH: i32 = 4;
+
+/// Coverage from which a pixel counts as selected
+const SELECTED_COVERAGE: u8 = 128;
+
+/// Pixels taken out of the document, with their opacity for transparent documents
+#[derive(Clone)]
+pub struct PixelBlock {
+    pub image: RgbImage,
+    pub alpha: Option<GrayImage>,
+    /// Selection coverage of the pixels, `None` when all of them are selected
+    pub mask: Option<GrayImage>,
+}
+
+impl PixelBlock {
+    /// Draws the pixels at the given screen position
+    pub fn draw(&self, x: i32, y: i32) {
+        let (w, h) = (self.image.width() as i32, self.image.height() as i32);
+        let img = if self.alpha.is_some() || self.mask.is_some() {
+            let data = self
+                .image
+                .enumerate_pixels()
+                .flat_map(|(px, py, c)| {
+                    let a = self
+                        .alpha
+                        .as_ref()
+                        .map_or(255, |a| a.get_pixel(px, py).0[0]);
+                    let m = self.mask.as_ref().map_or(255, |m| m.get_pixel(px, py).0[0]);
+                    [c.0[0], c.0[1], c.0[2], (a as u32 * m as u32 / 255) as u8]
+                })
+                .collect::<Vec<u8>>();
+            fltk::image::RgbImage::new(&data, w, h, enums::ColorDepth::Rgba8)
+        } else {
+            fltk::image::RgbImage::new(self.image.as_raw(), w, h, enums::ColorDepth::Rgb8)
+        };
+        if let Ok(mut img) = img {
+            img.draw(x, y, w, h);
+        }
+    }
+}
+
+/// How a new selection is combined with the existing one
+#[derive(Copy, Clone, PartialEq)]
+pub enum SelectionOp {
+    Replace,
+    Add,
+    Subtract,
+    Intersect,
+}
+
+impl SelectionOp {
+    /// Operation picked with modifier keys held when a selection tool starts.
+    /// Shift adds to the selection, Alt subtracts from it and both keep the overlap
+    pub fn from_modifiers() -> Self {
+        match (app::is_event_shift(), app::is_event_alt()) {
+            (true, true) => SelectionOp::Intersect,
+            (true, false) => SelectionOp::Add,
+            (false, true) => SelectionOp::Subtract,
+            (false, false) => SelectionOp::Replace,
+        }
+    }
+}
+
+/// Selected area of the document, painting only changes pixels inside it
+#[derive(Clone)]
+pub struct Selection {
+    /// Left, top, width and height in image pixels
+    pub bounds: (i32, i32, i32, i32),
+    /// Coverage of the pixels within the bounds, 255 is fully selected.
+    /// `None` when the whole rectangle is selected
+    pub mask: Option<GrayImage>,
+    /// Outline pixels of a masked selection relative to the bounds, found when first drawn
+    edges: OnceCell<Vec<(i32, i32)>>,
+}
+
+impl Selection {
+    pub fn new(bounds: (i32, i32, i32, i32), mask: Option<GrayImage>) -> Self {
+        Self {
+            bounds,
+            mask,
+            edges: OnceCell::new(),
+        }
+    }
+
+    pub fn rectangle(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Self::new((x, y, w, h), None)
+    }
+
+    /// Rectangle with corners at both points
+    pub fn from_corners(a: draw::Coord<i32>, b: draw::Coord<i32>) -> Self {
+        Self::rectangle(
+            a.0.min(b.0),
+            a.1.min(b.1),
+            (a.0 - b.0).abs() + 1,
+            (a.1 - b.1).abs() + 1,
+        )
+    }
+
+    /// Selection from the coverage of every pixel of the document, `None` if nothing is selected
+    pub fn from_mask(mask: &GrayImage) -> Option<Self> {
+        let (mut left, mut top) = (u32::MAX, u32::MAX);
+        let (mut right, mut bottom) = (0, 0);
+        for (x, y, p) in mask.enumerate_pixels() {
+            if p.0[0] > 0 {
+                left = left.min(x);
+                top = top.min(y);
+                right = right.max(x);
+                bottom = bottom.max(y);
+            }
+        }
+        if left > right || top > bottom {
+            return None;
+        }
+
+        let (w, h) = (right - left + 1, bottom - top + 1);
+        let mask = imageops::crop_imm(mask, left, top, w, h).to_image();
+        let bounds = (left as i32, top as i32, w as i32, h as i32);
+
+        // Fully covered rectangles do not need a mask
+        let mask = if mask.pixels().all(|p| p.0[0] == 255) {
+            None
+        } else {
+            Some(mask)
+        };
+        Some(Self::new(bounds, mask))
+    }
+
+    /// Pixels whose centers lie inside a polygon, crossing edges follow the even-odd rule
+    pub fn from_polygon(points: &[draw::Coord<i32>], size: (i32, i32)) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+
+        let mut mask = GrayImage::new(size.0 as u32, size.1 as u32);
+        let top = points.iter().map(|p| p.1).min()?.max(0);
+        let bottom = points.iter().map(|p| p.1).max()?.min(size.1 - 1);
+        let mut crossings = Vec::new();
+        for y in top..=bottom {
+            let cy = y as f64 + 0.5;
+            crossings.clear();
+            for (i, a) in points.iter().enumerate() {
+                let b = &points[(i + 1) % points.len()];
+                let (ay, by) = (a.1 as f64, b.1 as f64);
+                if (ay <= cy) != (by <= cy) {
+                    let t = (cy - ay) / (by - ay);
+                    crossings.push(a.0 as f64 + t * (b.0 - a.0) as f64);
+                }
+            }
+            crossings.sort_by(f64::total_cmp);
+
+            // Pixel centers between pairs of crossings are inside
+            for span in crossings.chunks_exact(2) {
+                let from = (span[0] - 0.5).ceil().max(0.0) as i32;
+                let to = ((span[1] - 0.5).ceil() as i32).min(size.0);
+                for x in from..to {
+                    mask.put_pixel(x as u32, y as u32, Luma([255]));
+                }
+            }
+        }
+
+        Self::from_mask(&mask)
+    }
+
+    /// How much of the pixel is selected, from 0 to 255
+    pub fn coverage(&self, x: i32, y: i32) -> u8 {
+        let (bx, by, bw, bh) = self.bounds;
+        if x < bx || y < by || x >= bx + bw || y >= by + bh {
+            return 0;
+        }
+        match &self.mask {
+            Some(mask) => mask.get_pixel((x - bx) as u32, (y - by) as u32).0[0],
+            None => 255,
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.coverage(x, y) >= SELECTED_COVERAGE
+    }
+
+    /// Part of the selection inside an image of the given size, `None` if nothing is left
+    pub fn clipped(&self, size: (i32, i32)) -> Option<Self> {
+        let (x, y, w, h) = self.bounds;
+        let (left, top) = (x.max(0), y.max(0));
+        let (right, bottom) = ((x + w).min(size.0), (y + h).min(size.1));
+        if left >= right || top >= bottom {
+            return None;
+        }
+
+        let (w, h) = (right - left, bottom - top);
+        let mask = self.mask.as_ref().map(|mask| {
+            imageops::crop_imm(
+                mask,
+                (left - x) as u32,
+                (top - y) as u32,
+                w as u32,
+                h as u32,
+            )
+            .to_image()
+        });
+        Some(Self::new((left, top, w, h), mask))
+    }
+
+    pub fn translated(&self, dx: i32, dy: i32) -> Self {
+        let (x, y, w, h) = self.bounds;
+        // The outline moves along, it is kept relative to the bounds
+        Self {
+            bounds: (x + dx, y + dy, w, h),
+            mask: self.mask.clone(),
+            edges: self.edges.clone(),
+        }
+    }
+
+    /// Coverage of every pixel of a document of the given size
+    pub fn to_mask(&self, size: (i32, i32)) -> GrayImage {
+        GrayImage::from_fn(size.0 as u32, size.1 as u32, |x, y| {
+            Luma([self.coverage(x as i32, y as i32)])
+        })
+    }
+
+    /// Applies a new selection to the current one
+    pub fn combine(
+        current: Option<&Selection>,
+        new: Option<Selection>,
+        op: SelectionOp,
+        size: (i32, i32),
+    ) -> Option<Selection> {
+        let merge = |a: &Selection, b: &Selection, f: fn(u32, u32) -> u32| {
+            let (a, b) = (a.to_mask(size), b.to_mask(size));
+            let mask = GrayImage::from_fn(size.0 as u32, size.1 as u32, |x, y| {
+                let (a, b) = (a.get_pixel(x, y).0[0], b.get_pixel(x, y).0[0]);
+                Luma([f(a as u32, b as u32) as u8])
+            });
+            Selection::from_mask(&mask)
+        };
+
+        match (op, current, new) {
+            (SelectionOp::Replace, _, new) => new,
+            (SelectionOp::Add, Some(current), Some(new)) => merge(current, &new, u32::max),
+            (SelectionOp::Add, current, new) => new.or_else(|| current.cloned()),
+            (SelectionOp::Subtract, Some(current), Some(new)) => {
+                merge(current, &new, |a, b| a * (255 - b) / 255)
+            }
+            (SelectionOp::Subtract, current, None) => current.cloned(),
+            (SelectionOp::Intersect, Some(current), Some(new)) => {
+                merge(current, &new, |a, b| a * b / 255)
+            }
+            (SelectionOp::Subtract | SelectionOp::Intersect, _, _) => None,
+        }
+    }
+
+    /// Softens the edge of the selection over about `radius` pixels
+    pub fn feathered(&self, radius: i32, size: (i32, i32)) -> Option<Self> {
+        if radius <= 0 {
+            return Some(self.clone());
+        }
+        let mask = imageops::blur(&self.to_mask(size), radius as f32 / 2.0);
+        Self::from_mask(&mask)
+    }
+
+    /// Selected pixels next to unselected ones, relative to the bounds
+    fn find_edges(&self) -> Vec<(i32, i32)> {
+        let (bx, by, bw, bh) = self.bounds;
+        let mut edges = Vec::new();
+        for y in by..by + bh {
+            for x in bx..bx + bw {
+                let on_edge = self.contains(x, y)
+                    && (!self.contains(x - 1, y)
+                        || !self.contains(x + 1, y)
+                        || !self.contains(x, y - 1)
+                        || !self.contains(x, y + 1));
+                if on_edge {
+                    edges.push((x - bx, y - by));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Draws the outline of the selection placed at `origin` on screen as marching ants,
+    /// increasing `phase` moves the dashes
+    pub fn draw_outline(&self, origin: (i32, i32), phase: i32) {
+        let (x, y, w, h) = self.bounds;
+        let (left, top) = (origin.0 + x, origin.1 + y);
+
+        let ant = |px: i32, py: i32| {
+            let color = if (px + py + phase).rem_euclid(2 * ANT_LENGTH) < ANT_LENGTH {
+                enums::Color::Black
+            } else {
+                enums::Color::White
+            };
+            draw::set_draw_color(color);
+            draw::draw_point(px, py);
+        };
+
+        if self.mask.is_some() {
+            for (dx, dy) in self.edges.get_or_init(|| self.find_edges()) {
+                ant(left + dx, top + dy);
+            }
+            return;
+        }
+
+        let (right, bottom) = (left + w - 1, top + h - 1);
+        for px in left..=right {
+            ant(px, top);
+            ant(px, bottom);
+        }
+        for py in top..=bottom {
+            ant(left, py);
+            ant(right, py);
+        }
+    }
+}
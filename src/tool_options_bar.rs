@@ -0,0 +1,107 @@
+use fltk::{prelude::*, *};
+
+use crate::tools::ToolOption;
+
+/// Approximate width of one character of a toolbar label
+const CHAR_WIDTH: i32 = 7;
+
+/// Part of the toolbar with widgets for the settings of the current tool
+pub struct ToolOptionsBar {
+    flex: group::Flex,
+}
+
+impl ToolOptionsBar {
+    pub fn new() -> Self {
+        let mut flex = group::Flex::default().row();
+        flex.set_margin(0);
+        flex.end();
+
+        Self { flex }
+    }
+
+    /// Replaces the widgets with ones for `options`. `on_change` gets the index
+    /// of the changed option and its new value as taken by [`crate::tools::Tool::set_option`]
+    pub fn set_options<F: Fn(usize, f64) + Clone + 'static>(
+        &mut self,
+        options: &[ToolOption],
+        on_change: F,
+    ) {
+        self.flex.clear();
+        self.flex.begin();
+
+        for (index, option) in options.iter().enumerate() {
+            match option {
+                ToolOption::Choice {
+                    label,
+                    items,
+                    value,
+                } => {
+                    self.add_label(label);
+
+                    let mut choice = menu::Choice::default();
+                    for item in items.iter() {
+                        // Menu labels take these characters as markup unless escaped
+                        let mut label = String::new();
+                        for c in item.chars() {
+                            if "&/\\_|".contains(c) {
+                                label.push('\\');
+                            }
+                            label.push(c);
+                        }
+                        choice.add_choice(&label);
+                    }
+                    choice.set_value(*value as i32);
+                    choice.set_callback({
+                        let on_change = on_change.clone();
+                        move |c| on_change(index, c.value() as f64)
+                    });
+                    let width = items.iter().map(|i| i.len() as i32).max().unwrap_or(0);
+                    self.flex.fixed(&choice, width * CHAR_WIDTH + 30);
+                }
+                ToolOption::Number {
+                    label,
+                    min,
+                    max,
+                    step,
+                    value,
+                } => {
+                    self.add_label(label);
+
+                    let mut spinner = misc::Spinner::default();
+                    spinner.set_range(*min, *max);
+                    spinner.set_step(*step);
+                    spinner.set_value(*value);
+                    spinner.set_callback({
+                        let on_change = on_change.clone();
+                        move |s| on_change(index, s.value())
+                    });
+                    self.flex.fixed(&spinner, 70);
+                }
+                ToolOption::Toggle { label, value } => {
+                    let mut check = button::CheckButton::default().with_label(label);
+                    check.set_checked(*value);
+                    check.set_callback({
+                        let on_change = on_change.clone();
+                        move |b| on_change(index, if b.is_checked() { 1.0 } else { 0.0 })
+                    });
+                    self.flex
+                        .fixed(&check, label.len() as i32 * CHAR_WIDTH + 30);
+                }
+            }
+        }
+
+        frame::Frame::default();
+
+        self.flex.end();
+        self.flex.layout();
+        self.flex.redraw();
+    }
+
+    fn add_label(&mut self, label: &str) {
+        let frame = frame::Frame::default()
+            .with_label(label)
+            .with_align(enums::Align::Right | enums::Align::Inside);
+        self.flex
+            .fixed(&frame, label.len() as i32 * CHAR_WIDTH + 10);
+    }
+}
@@ -0,0 +1,618 @@
+mod airbrush;
+mod brush;
+mod clone_stamp;
+mod curve;
+mod eraser;
+mod eyedropper;
+mod fill;
+mod gradient;
+mod lasso;
+mod line;
+mod pencil;
+mod polygon;
+mod retouch;
+mod select;
+mod shape;
+mod text;
+mod wand;
+
+use ::image::{GrayImage, Luma, Rgb, RgbImage};
+use fltk::{prelude::*, *};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::brush_tip::BrushTip;
+use crate::paint_canvas::{
+    capture_alpha, draw_surface_data, to_deep_channel, to_display_channel, DeepImage,
+};
+use crate::selection::{PixelBlock, Selection};
+
+pub use airbrush::Airbrush;
+pub use brush::Brush;
+pub use clone_stamp::CloneStamp;
+pub use curve::Curve;
+pub use eraser::Eraser;
+pub use eyedropper::Eyedropper;
+pub use fill::Fill;
+pub use gradient::Gradient;
+pub use lasso::Lasso;
+pub use line::Line;
+pub use pencil::Pencil;
+pub use polygon::Polygon;
+pub use retouch::{Retouch, RetouchKind};
+pub use select::RectangleSelect;
+pub use shape::{Shape, ShapeKind};
+pub use text::Text;
+pub use wand::MagicWand;
+
+pub type CoordOption = Option<draw::Coord<i32>>;
+
+/// Rectangle as left, top, width and height
+pub type Bounds = (i32, i32, i32, i32);
+
+pub const MIN_TOOL_SIZE: i32 = 1;
+pub const MAX_TOOL_SIZE: i32 = 200;
+
+/// Next tool size when growing or shrinking it by a step, steps are about 10% of the size
+pub fn step_tool_size(size: i32, grow: bool) -> i32 {
+    let step = (size / 10).max(1);
+    let size = if grow { size + step } else { size - step };
+    size.clamp(MIN_TOOL_SIZE, MAX_TOOL_SIZE)
+}
+
+/// Rotates `end` around `start` to the closest multiple of `step` degrees, keeping the length
+pub fn snap_angle(start: draw::Coord<i32>, end: draw::Coord<i32>, step: f64) -> draw::Coord<i32> {
+    let (dx, dy) = ((end.0 - start.0) as f64, (end.1 - start.1) as f64);
+    let length = dx.hypot(dy);
+    let step = step.to_radians();
+    let angle = (dy.atan2(dx) / step).round() * step;
+    draw::Coord(
+        start.0 + (length * angle.cos()).round() as i32,
+        start.1 + (length * angle.sin()).round() as i32,
+    )
+}
+
+/// Distance from `p` to the segment between `a` and `b`
+pub fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// Rectangle around the points, grown by `margin` on every side
+pub fn points_bounds(points: &[draw::Coord<i32>], margin: i32) -> Bounds {
+    let Some(first) = points.first() else {
+        return (0, 0, 0, 0);
+    };
+    let (left, top, right, bottom) = points
+        .iter()
+        .fold((first.0, first.1, first.0, first.1), |(l, t, r, b), p| {
+            (l.min(p.0), t.min(p.1), r.max(p.0), b.max(p.1))
+        });
+    (
+        left - margin,
+        top - margin,
+        right - left + 2 * margin + 1,
+        bottom - top + 2 * margin + 1,
+    )
+}
+
+/// Overlap of two rectangles, `None` when they do not overlap
+pub fn intersect_bounds(a: Bounds, b: Bounds) -> Option<Bounds> {
+    let (left, top) = (a.0.max(b.0), a.1.max(b.1));
+    let (right, bottom) = ((a.0 + a.2).min(b.0 + b.2), (a.1 + a.3).min(b.1 + b.3));
+    (left < right && top < bottom).then_some((left, top, right - left, bottom - top))
+}
+
+/// Adds the clicked vertex to a path built from clicks. Returns whether the click
+/// is the second one of a double-click, which ends the path; the first click
+/// has already added its last vertex
+pub fn add_vertex(points: &mut Vec<draw::Coord<i32>>, coord: draw::Coord<i32>) -> bool {
+    if app::event_clicks() && !points.is_empty() {
+        return true;
+    }
+    points.push(coord);
+    false
+}
+
+/// Moves the vertex that was just clicked while the button is held
+pub fn move_last_vertex(points: &mut [draw::Coord<i32>], coord: draw::Coord<i32>) {
+    if let Some(last) = points.last_mut() {
+        *last = coord;
+    }
+}
+
+/// Moves a document position to where drawing with the given origin puts it
+pub fn offset_coord(coord: draw::Coord<i32>, origin: (i32, i32)) -> draw::Coord<i32> {
+    draw::Coord(origin.0 + coord.0, origin.1 + coord.1)
+}
+
+/// Blends from one channel value to another, `amount` 255 gives `to`
+fn mix(from: u8, to: u8, amount: u8) -> u8 {
+    let amount = amount as u32;
+    ((from as u32 * (255 - amount) + to as u32 * amount + 127) / 255) as u8
+}
+
+/// Blends from one 16-bit channel value to another, `amount` 255 gives `to`
+fn mix_deep(from: u16, to: u16, amount: u8) -> u16 {
+    let amount = amount as u32;
+    ((from as u32 * (255 - amount) + to as u32 * amount + 127) / 255) as u16
+}
+
+/// Puts the colors of a block into a 16-bit document, mixed by the block mask if it has one.
+/// Blocks without a mask hold the result of 8-bit tools, so their pixels that keep
+/// the value the document shows leave the full precision value alone
+pub fn write_deep_block(deep: &mut DeepImage, position: (i32, i32), block: &PixelBlock) {
+    let (w, h) = (deep.pixels.width() as i32, deep.pixels.height() as i32);
+    for (px, py, src) in block.image.enumerate_pixels() {
+        let (x, y) = (position.0 + px as i32, position.1 + py as i32);
+        if x < 0 || y < 0 || x >= w || y >= h {
+            continue;
+        }
+        let (x, y) = (x as u32, y as u32);
+        let dst = *deep.pixels.get_pixel(x, y);
+        match &block.mask {
+            Some(mask) => {
+                let coverage = mask.get_pixel(px, py).0[0];
+                let color = std::array::from_fn(|c| {
+                    mix_deep(dst.0[c], to_deep_channel(src.0[c]), coverage)
+                });
+                deep.set_pixel(x, y, Rgb(color));
+            }
+            None if dst.0.map(to_display_channel) == src.0 => {}
+            None => deep.set_pixel(x, y, Rgb(src.0.map(to_deep_channel))),
+        }
+    }
+}
+
+/// Document state a tool works with while handling an event.
+/// Colors changed by the tool become the colors of the document
+pub struct ToolContext<'a> {
+    pub surf: &'a surface::ImageSurface,
+    /// Opacity mask of a transparent document
+    pub alpha_surf: Option<&'a surface::ImageSurface>,
+    pub size: (i32, i32),
+    pub fg_color: enums::Color,
+    pub bg_color: enums::Color,
+    /// Area that painting is limited to, shared with the canvas
+    pub selection: Rc<RefCell<Option<Selection>>>,
+    /// Pixels of a 16-bit document, which then take the place of the surface
+    pub deep_image: Rc<RefCell<Option<DeepImage>>>,
+}
+
+impl ToolContext<'_> {
+    /// Runs drawing commands on the surface and repeats them in white on the alpha mask
+    /// of a transparent document, so that painted pixels become opaque.
+    /// The commands stay within `area` and add the origin they get to their coordinates
+    pub fn paint<F: Fn(enums::Color, (i32, i32))>(&self, color: enums::Color, area: Bounds, f: F) {
+        self.paint_surfaces(color, enums::Color::White, area, f);
+    }
+
+    /// Runs drawing commands that remove paint. Opaque documents get the background color,
+    /// transparent documents become see-through
+    pub fn erase<F: Fn(enums::Color, (i32, i32))>(&self, area: Bounds, f: F) {
+        self.paint_surfaces(self.bg_color, enums::Color::Black, area, f);
+    }
+
+    fn paint_surfaces<F: Fn(enums::Color, (i32, i32))>(
+        &self,
+        color: enums::Color,
+        alpha_color: enums::Color,
+        area: Bounds,
+        f: F,
+    ) {
+        let selection = self.selection.borrow();
+        if self.deep_image.borrow().is_some() {
+            self.paint_deep(color, alpha_color, area, selection.as_ref(), f);
+            return;
+        }
+
+        // Drawing outside of the selection is clipped away
+        let clip = selection.as_ref().map(|s| s.bounds);
+        let draw_clipped = |color| match clip {
+            Some((x, y, w, h)) => {
+                draw::push_clip(x, y, w, h);
+                f(color, (0, 0));
+                draw::pop_clip();
+            }
+            None => f(color, (0, 0)),
+        };
+
+        // Selections with a mask also need the pixels from before to restore what is not covered
+        let masked = selection.as_ref().filter(|s| s.mask.is_some());
+        let before = masked.and_then(|_| Some((self.read_image()?, self.read_alpha())));
+
+        surface::ImageSurface::push_current(self.surf);
+        draw_clipped(color);
+        surface::ImageSurface::pop_current();
+
+        if let Some(alpha_surf) = self.alpha_surf {
+            surface::ImageSurface::push_current(alpha_surf);
+            draw_clipped(alpha_color);
+            surface::ImageSurface::pop_current();
+        }
+
+        if let (Some(selection), Some((img, alpha))) = (masked, before) {
+            self.restore_unselected(selection, &img, alpha.as_ref());
+        }
+    }
+
+    /// Paints a 16-bit document. Drawing commands run in white over black on a scratch
+    /// surface the size of the painted area, which tells how much of each pixel they cover
+    fn paint_deep<F: Fn(enums::Color, (i32, i32))>(
+        &self,
+        color: enums::Color,
+        alpha_color: enums::Color,
+        area: Bounds,
+        selection: Option<&Selection>,
+        f: F,
+    ) {
+        let area = intersect_bounds(area, (0, 0, self.size.0, self.size.1));
+        let area = match selection {
+            Some(selection) => area.and_then(|area| intersect_bounds(area, selection.bounds)),
+            None => area,
+        };
+        let Some((left, top, w, h)) = area else {
+            return;
+        };
+        let size = (w, h);
+
+        let scratch = surface::ImageSurface::new(size.0, size.1, false);
+        surface::ImageSurface::push_current(&scratch);
+        draw::draw_rect_fill(0, 0, size.0, size.1, enums::Color::Black);
+        f(enums::Color::White, (-left, -top));
+        surface::ImageSurface::pop_current();
+        let Some(coverage) = capture_alpha(&scratch, size) else {
+            return;
+        };
+
+        let mask = GrayImage::from_fn(size.0 as u32, size.1 as u32, |px, py| {
+            let (x, y) = (left + px as i32, top + py as i32);
+            let selected = selection.map_or(255, |s| s.coverage(x, y) as u32);
+            Luma([(coverage.get_pixel(px, py).0[0] as u32 * selected / 255) as u8])
+        });
+        let (w, h) = mask.dimensions();
+        let (r, g, b) = color.to_rgb();
+        let block = PixelBlock {
+            image: RgbImage::from_pixel(w, h, Rgb([r, g, b])),
+            alpha: Some(GrayImage::from_pixel(w, h, Luma([alpha_color.to_rgb().0]))),
+            mask: Some(mask),
+        };
+        self.write_block((left, top), &block);
+    }
+
+    /// Mixes pixels painted within the bounds of a masked selection with their previous
+    /// values, so that paint only shows as much as the pixel is selected
+    fn restore_unselected(
+        &self,
+        selection: &Selection,
+        before: &RgbImage,
+        before_alpha: Option<&GrayImage>,
+    ) {
+        let Some(mut img) = self.read_image() else {
+            return;
+        };
+        let mut alpha = self.read_alpha();
+
+        let (bx, by, bw, bh) = selection.bounds;
+        for y in by.max(0)..(by + bh).min(self.size.1) {
+            for x in bx.max(0)..(bx + bw).min(self.size.0) {
+                let coverage = selection.coverage(x, y);
+                let (x, y) = (x as u32, y as u32);
+                let old = before.get_pixel(x, y);
+                let new = img.get_pixel_mut(x, y);
+                for c in 0..3 {
+                    new.0[c] = mix(old.0[c], new.0[c], coverage);
+                }
+                if let (Some(alpha), Some(before_alpha)) = (alpha.as_mut(), before_alpha) {
+                    let new = alpha.get_pixel_mut(x, y);
+                    new.0[0] = mix(before_alpha.get_pixel(x, y).0[0], new.0[0], coverage);
+                }
+            }
+        }
+
+        self.write_image(&img, alpha.as_ref());
+    }
+
+    /// Whether painting may change the pixel, it has to be inside the selection if there is one
+    pub fn is_selected(&self, x: i32, y: i32) -> bool {
+        self.selection
+            .borrow()
+            .as_ref()
+            .is_none_or(|s| s.contains(x, y))
+    }
+
+    /// Reads the pixels of the document, 16-bit documents give the 8 bits tools work with
+    pub fn read_image(&self) -> Option<RgbImage> {
+        if let Some(deep) = self.deep_image.borrow().as_ref() {
+            return Some(deep.display.clone());
+        }
+
+        let img = draw::capture_surface(self.surf, self.size.0, self.size.1).ok()?;
+        RgbImage::from_raw(self.size.0 as u32, self.size.1 as u32, img.to_rgb_data())
+    }
+
+    /// Reads the opacity of a transparent document
+    pub fn read_alpha(&self) -> Option<GrayImage> {
+        capture_alpha(self.alpha_surf?, self.size)
+    }
+
+    /// Reads the pixels inside of a rectangle that lies within the document
+    pub fn read_block(&self, (x, y, w, h): (i32, i32, i32, i32)) -> Option<PixelBlock> {
+        use ::image::imageops::crop_imm;
+
+        let (x, y, w, h) = (x as u32, y as u32, w as u32, h as u32);
+        let image = crop_imm(&self.read_image()?, x, y, w, h).to_image();
+        let alpha = self
+            .read_alpha()
+            .map(|alpha| crop_imm(&alpha, x, y, w, h).to_image());
+        Some(PixelBlock {
+            image,
+            alpha,
+            mask: None,
+        })
+    }
+
+    /// Draws pixels with their top left corner at `position`, mixed by the block mask
+    /// if it has one. Blocks without opacity become opaque in transparent documents
+    pub fn write_block(&self, position: (i32, i32), block: &PixelBlock) {
+        let deep = self
+            .deep_image
+            .borrow_mut()
+            .as_mut()
+            .map(|deep| write_deep_block(deep, position, block))
+            .is_some();
+        if !deep {
+            self.write_block_colors(position, block);
+        }
+
+        if let Some(alpha_surf) = self.alpha_surf {
+            self.write_block_alpha(alpha_surf, position, block);
+        }
+    }
+
+    fn write_block_colors(&self, position: (i32, i32), block: &PixelBlock) {
+        let (w, h) = (block.image.width() as i32, block.image.height() as i32);
+        let Some(mask) = &block.mask else {
+            surface::ImageSurface::push_current(self.surf);
+            let _ = draw::draw_image(
+                block.image.as_raw(),
+                position.0,
+                position.1,
+                w,
+                h,
+                enums::ColorDepth::Rgb8,
+            );
+            surface::ImageSurface::pop_current();
+            return;
+        };
+
+        let Some(mut img) = self.read_image() else {
+            return;
+        };
+        for (px, py, coverage) in mask.enumerate_pixels() {
+            let (x, y) = (position.0 + px as i32, position.1 + py as i32);
+            if x < 0 || y < 0 || x >= self.size.0 || y >= self.size.1 {
+                continue;
+            }
+            let src = block.image.get_pixel(px, py);
+            let dst = img.get_pixel_mut(x as u32, y as u32);
+            for c in 0..3 {
+                dst.0[c] = mix(dst.0[c], src.0[c], coverage.0[0]);
+            }
+        }
+        draw_surface_data(self.surf, img.as_raw(), self.size, enums::ColorDepth::Rgb8);
+    }
+
+    fn write_block_alpha(
+        &self,
+        alpha_surf: &surface::ImageSurface,
+        position: (i32, i32),
+        block: &PixelBlock,
+    ) {
+        let (w, h) = (block.image.width() as i32, block.image.height() as i32);
+        let Some(mask) = &block.mask else {
+            surface::ImageSurface::push_current(alpha_surf);
+            match &block.alpha {
+                Some(alpha) => {
+                    let _ = draw::draw_image(
+                        alpha.as_raw(),
+                        position.0,
+                        position.1,
+                        w,
+                        h,
+                        enums::ColorDepth::L8,
+                    );
+                }
+                None => draw::draw_rect_fill(position.0, position.1, w, h, enums::Color::White),
+            }
+            surface::ImageSurface::pop_current();
+            return;
+        };
+
+        let Some(mut alpha) = self.read_alpha() else {
+            return;
+        };
+        for (px, py, coverage) in mask.enumerate_pixels() {
+            let (x, y) = (position.0 + px as i32, position.1 + py as i32);
+            if x < 0 || y < 0 || x >= self.size.0 || y >= self.size.1 {
+                continue;
+            }
+            let src = block
+                .alpha
+                .as_ref()
+                .map_or(255, |a| a.get_pixel(px, py).0[0]);
+            let dst = alpha.get_pixel_mut(x as u32, y as u32);
+            dst.0[0] = mix(dst.0[0], src, coverage.0[0]);
+        }
+        draw_surface_data(alpha_surf, alpha.as_raw(), self.size, enums::ColorDepth::L8);
+    }
+
+    /// Replaces the pixels of an 8-bit document, `alpha` is only used by transparent documents
+    fn write_image(&self, img: &RgbImage, alpha: Option<&GrayImage>) {
+        draw_surface_data(self.surf, img.as_raw(), self.size, enums::ColorDepth::Rgb8);
+        if let (Some(alpha_surf), Some(alpha)) = (self.alpha_surf, alpha) {
+            draw_surface_data(alpha_surf, alpha.as_raw(), self.size, enums::ColorDepth::L8);
+        }
+    }
+
+    /// Color of tool outlines that stays visible over the document
+    pub fn overlay_color(&self) -> enums::Color {
+        enums::Color::contrast(self.fg_color, self.bg_color)
+    }
+
+    /// Outlines the round tip of a tool of the given size around the pointer
+    pub fn draw_size_circle(&self, origin: (i32, i32), coord: CoordOption, size: i32) {
+        if let Some(c) = coord {
+            draw::set_draw_color(self.overlay_color());
+            draw::set_line_style(draw::LineStyle::Solid, 1);
+            draw::draw_circle(
+                (origin.0 + c.0) as f64,
+                (origin.1 + c.1) as f64,
+                (size as f64) / 2.0,
+            );
+        }
+    }
+
+    /// Part of the document covered by a round tip of the given size moved from `from`
+    /// to `to`, `None` when the stroke lies outside of the document
+    pub fn stroke_bounds(
+        &self,
+        from: draw::Coord<i32>,
+        to: draw::Coord<i32>,
+        size: i32,
+    ) -> Option<Bounds> {
+        let bounds = points_bounds(&[from, to], size / 2 + 1);
+        intersect_bounds(bounds, (0, 0, self.size.0, self.size.1))
+    }
+}
+
+/// Setting of a tool shown in the toolbar next to the size
+pub enum ToolOption {
+    /// One of several named values, the value is the index of the item
+    Choice {
+        label: &'static str,
+        items: Vec<String>,
+        value: usize,
+    },
+    Number {
+        label: &'static str,
+        min: f64,
+        max: f64,
+        step: f64,
+        value: f64,
+    },
+    Toggle {
+        label: &'static str,
+        value: bool,
+    },
+}
+
+/// Drawing instrument that receives mouse events of the canvas.
+/// Coordinates are in image pixels
+pub trait Tool {
+    /// Label in the Tools menu
+    fn name(&self) -> &'static str;
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None
+    }
+
+    /// Whether Alt changes how the tool works. Pressing the mouse with Alt held
+    /// turns other tools into the eyedropper until the button is released
+    fn uses_alt(&self) -> bool {
+        false
+    }
+
+    /// Diameter of the tool in pixels, `None` for tools without a size
+    fn size(&self) -> Option<i32> {
+        None
+    }
+
+    fn set_size(&mut self, _size: i32) {}
+
+    /// Whether the tool paints with the size of the brush, changing the size of one
+    /// of these tools changes it for all of them
+    fn uses_brush_size(&self) -> bool {
+        false
+    }
+
+    /// Tools that stamp a tip along their strokes take the tip picked in the brush panel
+    fn set_brush_tip(&mut self, _tip: &BrushTip) {}
+
+    /// Settings shown in the toolbar while the tool is active
+    fn options(&self) -> Vec<ToolOption> {
+        Vec::new()
+    }
+
+    /// Changes the setting at `index` of [`Tool::options`].
+    /// Choices get the index of the item, numbers their value and toggles 0 or 1
+    fn set_option(&mut self, _index: usize, _value: f64) {}
+
+    fn push(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {}
+
+    fn drag(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {}
+
+    fn released(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {}
+
+    fn moved(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {}
+
+    /// Seconds between [`Tool::repeat`] calls while a mouse button is held,
+    /// `None` for tools that only react to mouse events
+    fn repeat_interval(&self) -> Option<f64> {
+        None
+    }
+
+    /// Keeps working over time while a mouse button is held, even if the pointer stays still
+    fn repeat(&mut self, _ctx: &mut ToolContext) {}
+
+    /// Handles a key pressed while the canvas has focus, keys that are not used
+    /// go on to menu shortcuts
+    fn key(&mut self, _ctx: &mut ToolContext, _key: enums::Key) -> bool {
+        false
+    }
+
+    /// Commits unfinished work before another tool is selected
+    fn finish(&mut self, _ctx: &mut ToolContext) {}
+
+    /// Draws previews and outlines on top of the image placed at `origin` on screen.
+    /// `coord` is the pointer position if it is over the canvas
+    fn draw_overlay(&self, _ctx: &ToolContext, _origin: (i32, i32), _coord: CoordOption) {}
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Default
+    }
+}
+
+/// Every tool available in the Tools menu, the first one is active at startup
+pub fn all_tools() -> Vec<Box<dyn Tool>> {
+    vec![
+        Box::new(Brush::new()),
+        Box::new(Pencil::new()),
+        Box::new(Airbrush::new()),
+        Box::new(Eraser::new()),
+        Box::new(CloneStamp::new()),
+        Box::new(Retouch::new(RetouchKind::Smudge)),
+        Box::new(Retouch::new(RetouchKind::Blur)),
+        Box::new(Retouch::new(RetouchKind::Sharpen)),
+        Box::new(Fill::new()),
+        Box::new(Gradient::new()),
+        Box::new(Eyedropper::new()),
+        Box::new(Line::new()),
+        Box::new(Shape::new(ShapeKind::Rectangle)),
+        Box::new(Shape::new(ShapeKind::RoundedRectangle)),
+        Box::new(Shape::new(ShapeKind::Ellipse)),
+        Box::new(Polygon::new()),
+        Box::new(Curve::new()),
+        Box::new(Text::new()),
+        Box::new(RectangleSelect::new()),
+        Box::new(Lasso::freehand()),
+        Box::new(Lasso::polygonal()),
+        Box::new(MagicWand::new()),
+    ]
+}
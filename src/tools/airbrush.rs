@@ -0,0 +1,172 @@
+use fltk::*;
+
+use super::{points_bounds, CoordOption, Tool, ToolContext, ToolOption};
+
+/// Time between sprays while the mouse button is held, in seconds
+const SPRAY_INTERVAL: f64 = 1.0 / 30.0;
+
+/// Xorshift generator, the same seed gives the same spray pattern
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero
+        Self(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform value in `0.0..1.0`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Scatters `count` dots evenly over the disc of `radius` around `center`.
+/// Dots are rounded towards the center, so none lands outside of the disc
+fn spray_dots(
+    rng: &mut Rng,
+    center: draw::Coord<i32>,
+    radius: f64,
+    count: i32,
+) -> Vec<draw::Coord<i32>> {
+    // Square root of the distance spreads dots evenly over the disc
+    (0..count)
+        .map(|_| {
+            let distance = radius * rng.next_f64().sqrt();
+            let angle = std::f64::consts::TAU * rng.next_f64();
+            draw::Coord(
+                center.0 + (distance * angle.cos()).trunc() as i32,
+                center.1 + (distance * angle.sin()).trunc() as i32,
+            )
+        })
+        .collect()
+}
+
+/// Spray of random dots around the pointer that keeps adding paint while the button is held
+pub struct Airbrush {
+    size: i32,
+    /// Dots sprayed at a time
+    density: i32,
+    coord: CoordOption,
+    rng: Rng,
+}
+
+impl Airbrush {
+    pub fn new() -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self {
+            size: 30,
+            density: 20,
+            coord: None,
+            rng: Rng::new(seed),
+        }
+    }
+
+    fn spray(&mut self, ctx: &ToolContext) {
+        let Some(center) = self.coord else {
+            return;
+        };
+
+        let radius = self.size as f64 / 2.0;
+        let dots = spray_dots(&mut self.rng, center, radius, self.density);
+
+        ctx.paint(ctx.fg_color, points_bounds(&dots, 0), |color, origin| {
+            draw::set_draw_color(color);
+            for p in &dots {
+                draw::draw_point(origin.0 + p.0, origin.1 + p.1);
+            }
+        });
+    }
+}
+
+impl Tool for Airbrush {
+    fn name(&self) -> &'static str {
+        "Airbrush"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'a'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![ToolOption::Number {
+            label: "Density:",
+            min: 1.0,
+            max: 200.0,
+            step: 1.0,
+            value: self.density as f64,
+        }]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        if index == 0 {
+            self.density = value.clamp(1.0, 200.0) as i32;
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.coord = Some(coord);
+        self.spray(ctx);
+    }
+
+    fn drag(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        // Paint comes with time, moving faster leaves a thinner trail
+        self.coord = Some(coord);
+    }
+
+    fn released(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {
+        self.coord = None;
+    }
+
+    fn repeat_interval(&self) -> Option<f64> {
+        Some(SPRAY_INTERVAL)
+    }
+
+    fn repeat(&mut self, ctx: &mut ToolContext) {
+        self.spray(ctx);
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        ctx.draw_size_circle(origin, coord, self.size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprayed(seed: u64, center: draw::Coord<i32>) -> Vec<(i32, i32)> {
+        spray_dots(&mut Rng::new(seed), center, 15.0, 500)
+            .iter()
+            .map(|p| (p.0, p.1))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_sprays_same_dots_inside_radius() {
+        let center = draw::Coord(50, 40);
+        let dots = sprayed(7, center);
+        assert_eq!(dots, sprayed(7, center));
+        assert_ne!(dots, sprayed(8, center));
+        for (x, y) in dots {
+            let distance = ((x - center.0) as f64).hypot((y - center.1) as f64);
+            assert!(distance <= 15.0, "dot at ({}, {}) is outside", x, y);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use ::image::{GrayImage, Luma, RgbImage};
+use fltk::*;
+
+use super::{mix, CoordOption, Tool, ToolContext, ToolOption};
+use crate::brush_tip::BrushTip;
+use crate::selection::PixelBlock;
+
+/// Left, top, right and bottom of an area changed by the stroke, right and bottom excluded
+type Dirty = Option<(i32, i32, i32, i32)>;
+
+/// Paint of the stroke in progress, kept apart from the document so that
+/// overlapping dabs never go past the opacity of the brush
+struct Stroke {
+    /// Document as it was when the stroke started
+    base: RgbImage,
+    base_alpha: Option<GrayImage>,
+    /// How much of each pixel the stroke covers
+    coverage: GrayImage,
+    colors: RgbImage,
+    dab_colors: RgbImage,
+    dab_coverage: GrayImage,
+    last: (f64, f64),
+    /// Distance left to travel before the next dab
+    to_next_dab: f64,
+}
+
+/// Brush stamping dabs of its tip along the stroke at even distances, so strokes
+/// look the same however fast the pointer moves. Flow is the paint each dab adds
+/// and opacity the most paint the whole stroke leaves, both in percent
+pub struct Brush {
+    size: i32,
+    tip: BrushTip,
+    opacity: i32,
+    /// Part of the round tip radius painted without falloff, in percent
+    hardness: i32,
+    /// Distance between dabs in percent of the diameter
+    spacing: i32,
+    flow: i32,
+    stroke: Option<Stroke>,
+}
+
+impl Brush {
+    pub fn new() -> Self {
+        Self {
+            size: 5,
+            tip: BrushTip::Round,
+            opacity: 100,
+            hardness: 100,
+            spacing: 25,
+            flow: 100,
+            stroke: None,
+        }
+    }
+
+    fn spacing_distance(&self) -> f64 {
+        (self.size as f64 * self.spacing as f64 / 100.0).max(1.0)
+    }
+
+    /// Adds one dab centered at `center` to the stroke
+    fn add_dab(&self, stroke: &mut Stroke, center: (f64, f64), dirty: &mut Dirty) {
+        let (w, h) = (stroke.dab_coverage.width(), stroke.dab_coverage.height());
+        let ox = center.0.round() as i32 - w as i32 / 2;
+        let oy = center.1.round() as i32 - h as i32 / 2;
+        let (doc_w, doc_h) = (
+            stroke.coverage.width() as i32,
+            stroke.coverage.height() as i32,
+        );
+
+        let left = ox.max(0);
+        let top = oy.max(0);
+        let right = (ox + w as i32).min(doc_w);
+        let bottom = (oy + h as i32).min(doc_h);
+        if left >= right || top >= bottom {
+            return;
+        }
+        *dirty = Some(match *dirty {
+            Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+            None => (left, top, right, bottom),
+        });
+
+        let flow = self.flow as f64 / 100.0;
+        for y in top..bottom {
+            for x in left..right {
+                let (px, py) = ((x - ox) as u32, (y - oy) as u32);
+                let a = stroke.dab_coverage.get_pixel(px, py).0[0] as f64 / 255.0 * flow;
+                if a <= 0.0 {
+                    continue;
+                }
+
+                // Dab goes over the paint already laid down by the stroke
+                let (x, y) = (x as u32, y as u32);
+                let old = stroke.coverage.get_pixel(x, y).0[0] as f64 / 255.0;
+                let new = old + a * (1.0 - old);
+                let src = stroke.dab_colors.get_pixel(px, py).0;
+                let dst = stroke.colors.get_pixel_mut(x, y);
+                for (d, s) in dst.0.iter_mut().zip(src) {
+                    *d = ((*d as f64 * old * (1.0 - a) + s as f64 * a) / new).round() as u8;
+                }
+                stroke
+                    .coverage
+                    .put_pixel(x, y, Luma([(new * 255.0).round() as u8]));
+            }
+        }
+    }
+
+    /// Puts the stroke over the document it started on, within the changed area
+    fn composite(&self, ctx: &ToolContext, stroke: &Stroke, dirty: Dirty) {
+        let Some((left, top, right, bottom)) = dirty else {
+            return;
+        };
+        let (w, h) = ((right - left) as u32, (bottom - top) as u32);
+
+        let opacity = self.opacity as u32;
+        let selection = ctx.selection.borrow();
+        let amount = |x: u32, y: u32| {
+            let selected = selection
+                .as_ref()
+                .map_or(255, |s| s.coverage(x as i32, y as i32) as u32);
+            let coverage = stroke.coverage.get_pixel(x, y).0[0] as u32;
+            (coverage * opacity / 100 * selected / 255) as u8
+        };
+
+        let image = RgbImage::from_fn(w, h, |px, py| {
+            let (x, y) = (left as u32 + px, top as u32 + py);
+            let (base, paint) = (stroke.base.get_pixel(x, y), stroke.colors.get_pixel(x, y));
+            let amount = amount(x, y);
+            let mut p = *base;
+            for c in 0..3 {
+                p.0[c] = mix(base.0[c], paint.0[c], amount);
+            }
+            p
+        });
+        let alpha = stroke.base_alpha.as_ref().map(|base_alpha| {
+            GrayImage::from_fn(w, h, |px, py| {
+                let (x, y) = (left as u32 + px, top as u32 + py);
+                Luma([mix(base_alpha.get_pixel(x, y).0[0], 255, amount(x, y))])
+            })
+        });
+        drop(selection);
+
+        let block = PixelBlock {
+            image,
+            alpha,
+            mask: None,
+        };
+        ctx.write_block((left, top), &block);
+    }
+}
+
+impl Tool for Brush {
+    fn name(&self) -> &'static str {
+        "Brush"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'b'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn uses_brush_size(&self) -> bool {
+        true
+    }
+
+    fn set_brush_tip(&mut self, tip: &BrushTip) {
+        self.tip = tip.clone();
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        let percent = |label, min, max, value: i32| ToolOption::Number {
+            label,
+            min,
+            max,
+            step: 1.0,
+            value: value as f64,
+        };
+        vec![
+            percent("Opacity:", 1.0, 100.0, self.opacity),
+            percent("Hardness:", 0.0, 100.0, self.hardness),
+            percent("Spacing:", 1.0, 200.0, self.spacing),
+            percent("Flow:", 1.0, 100.0, self.flow),
+        ]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        match index {
+            0 => self.opacity = value.clamp(1.0, 100.0) as i32,
+            1 => self.hardness = value.clamp(0.0, 100.0) as i32,
+            2 => self.spacing = value.clamp(1.0, 200.0) as i32,
+            3 => self.flow = value.clamp(1.0, 100.0) as i32,
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        let Some(base) = ctx.read_image() else {
+            return;
+        };
+        let (dab_colors, dab_coverage) = self.tip.render(
+            self.size,
+            self.hardness as f64 / 100.0,
+            ctx.fg_color.to_rgb(),
+        );
+        let (w, h) = (base.width(), base.height());
+        let mut stroke = Stroke {
+            base,
+            base_alpha: ctx.read_alpha(),
+            coverage: GrayImage::new(w, h),
+            colors: RgbImage::new(w, h),
+            dab_colors,
+            dab_coverage,
+            last: (coord.0 as f64, coord.1 as f64),
+            to_next_dab: self.spacing_distance(),
+        };
+
+        let mut dirty = None;
+        let center = stroke.last;
+        self.add_dab(&mut stroke, center, &mut dirty);
+        self.composite(ctx, &stroke, dirty);
+        self.stroke = Some(stroke);
+    }
+
+    fn drag(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        let Some(mut stroke) = self.stroke.take() else {
+            return;
+        };
+
+        // Distance to the next dab carries over between mouse events
+        let (x0, y0) = stroke.last;
+        let (dx, dy) = (coord.0 as f64 - x0, coord.1 as f64 - y0);
+        let length = dx.hypot(dy);
+        let spacing = self.spacing_distance();
+        let mut travelled = 0.0;
+        let mut dirty = None;
+        while stroke.to_next_dab <= length - travelled {
+            travelled += stroke.to_next_dab;
+            let t = travelled / length;
+            self.add_dab(&mut stroke, (x0 + dx * t, y0 + dy * t), &mut dirty);
+            stroke.to_next_dab = spacing;
+        }
+        stroke.to_next_dab -= length - travelled;
+        stroke.last = (coord.0 as f64, coord.1 as f64);
+
+        self.composite(ctx, &stroke, dirty);
+        self.stroke = Some(stroke);
+    }
+
+    fn released(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {
+        self.stroke = None;
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        ctx.draw_size_circle(origin, coord, self.size);
+    }
+}
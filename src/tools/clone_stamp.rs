@@ -0,0 +1,189 @@
+use ::image::imageops::crop_imm;
+use ::image::{GrayImage, RgbImage};
+use fltk::*;
+
+use super::{mix, segment_distance, CoordOption, Tool, ToolContext, ToolOption};
+use crate::selection::PixelBlock;
+
+/// Pixels of a clone stroke in progress
+struct Stroke {
+    /// Document as it was when the stroke started, strokes do not copy their own paint
+    source: RgbImage,
+    source_alpha: Option<GrayImage>,
+    /// Document with the paint of the stroke so far
+    painted: RgbImage,
+    painted_alpha: Option<GrayImage>,
+}
+
+/// Paints with pixels copied from another part of the document. Ctrl+click sets the
+/// source, strokes then copy from the same offset. Aligned strokes keep the offset of
+/// the first stroke, otherwise every stroke starts again from the source point.
+/// The stamp has the size of the brush
+pub struct CloneStamp {
+    size: i32,
+    aligned: bool,
+    source: CoordOption,
+    /// Distance from the painted pixel to the copied one
+    offset: Option<(i32, i32)>,
+    stroke: Option<Stroke>,
+    last_coord: CoordOption,
+}
+
+impl CloneStamp {
+    pub fn new() -> Self {
+        Self {
+            size: 20,
+            aligned: true,
+            source: None,
+            offset: None,
+            stroke: None,
+            last_coord: None,
+        }
+    }
+
+    /// Copies source pixels under the stroke segment from `from` to `to`
+    fn stamp(&mut self, ctx: &ToolContext, from: draw::Coord<i32>, to: draw::Coord<i32>) {
+        let (Some(stroke), Some((dx, dy))) = (self.stroke.as_mut(), self.offset) else {
+            return;
+        };
+
+        let Some((left, top, w, h)) = ctx.stroke_bounds(from, to, self.size) else {
+            return;
+        };
+        let (right, bottom) = (left + w, top + h);
+        let (w, h) = (w as u32, h as u32);
+        let radius = self.size as f64 / 2.0;
+
+        // Copied pixels show as much as they are selected
+        let (a, b) = ((from.0 as f64, from.1 as f64), (to.0 as f64, to.1 as f64));
+        let selection = ctx.selection.borrow();
+        for y in top..bottom {
+            for x in left..right {
+                let (sx, sy) = (x + dx, y + dy);
+                let inside = segment_distance((x as f64, y as f64), a, b) <= radius;
+                if !inside || sx < 0 || sy < 0 || sx >= ctx.size.0 || sy >= ctx.size.1 {
+                    continue;
+                }
+                let amount = selection.as_ref().map_or(255, |s| s.coverage(x, y));
+
+                let (x, y, sx, sy) = (x as u32, y as u32, sx as u32, sy as u32);
+                let src = *stroke.source.get_pixel(sx, sy);
+                let dst = stroke.painted.get_pixel_mut(x, y);
+                for c in 0..3 {
+                    dst.0[c] = mix(dst.0[c], src.0[c], amount);
+                }
+                if let (Some(painted), Some(source)) =
+                    (stroke.painted_alpha.as_mut(), &stroke.source_alpha)
+                {
+                    let dst = painted.get_pixel_mut(x, y);
+                    dst.0[0] = mix(dst.0[0], source.get_pixel(sx, sy).0[0], amount);
+                }
+            }
+        }
+        drop(selection);
+
+        let (x, y) = (left as u32, top as u32);
+        let block = PixelBlock {
+            image: crop_imm(&stroke.painted, x, y, w, h).to_image(),
+            alpha: stroke
+                .painted_alpha
+                .as_ref()
+                .map(|alpha| crop_imm(alpha, x, y, w, h).to_image()),
+            mask: None,
+        };
+        ctx.write_block((left, top), &block);
+    }
+}
+
+impl Tool for CloneStamp {
+    fn name(&self) -> &'static str {
+        "Clone Stamp"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'k'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn uses_brush_size(&self) -> bool {
+        true
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![ToolOption::Toggle {
+            label: "Aligned",
+            value: self.aligned,
+        }]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        if index == 0 {
+            self.aligned = value != 0.0;
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if app::is_event_ctrl() {
+            self.source = Some(coord);
+            self.offset = None;
+            return;
+        }
+        let Some(source) = self.source else {
+            return;
+        };
+
+        if !self.aligned || self.offset.is_none() {
+            self.offset = Some((source.0 - coord.0, source.1 - coord.1));
+        }
+        self.stroke = ctx.read_image().map(|source| {
+            let source_alpha = ctx.read_alpha();
+            Stroke {
+                painted: source.clone(),
+                painted_alpha: source_alpha.clone(),
+                source,
+                source_alpha,
+            }
+        });
+        self.stamp(ctx, coord, coord);
+        self.last_coord = Some(coord);
+    }
+
+    fn drag(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(c) = self.last_coord {
+            self.stamp(ctx, c, coord);
+            self.last_coord = Some(coord);
+        }
+    }
+
+    fn released(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {
+        self.last_coord = None;
+        self.stroke = None;
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        draw::set_draw_color(ctx.overlay_color());
+        draw::set_line_style(draw::LineStyle::Solid, 1);
+
+        // Cross where pixels are copied from
+        let source = match (coord, self.offset) {
+            (Some(c), Some((dx, dy))) if self.aligned || self.last_coord.is_some() => {
+                Some(draw::Coord(c.0 + dx, c.1 + dy))
+            }
+            _ => self.source,
+        };
+        if let Some(s) = source {
+            let (x, y) = (origin.0 + s.0, origin.1 + s.1);
+            draw::draw_line(x - 5, y, x + 5, y);
+            draw::draw_line(x, y - 5, x, y + 5);
+        }
+
+        ctx.draw_size_circle(origin, coord, self.size);
+    }
+}
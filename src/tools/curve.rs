@@ -0,0 +1,138 @@
+use fltk::*;
+
+use super::{points_bounds, CoordOption, Tool, ToolContext};
+
+/// Point of the curve that the next drag places
+#[derive(Copy, Clone, PartialEq)]
+enum Stage {
+    Idle,
+    Ends,
+    FirstControl,
+    SecondControl,
+}
+
+/// Cubic Bezier curve. The first drag places the end points, the next two
+/// drags bend the curve with the control points and the last release commits it
+pub struct Curve {
+    size: i32,
+    stage: Stage,
+    /// Start, first control, second control and end point
+    points: [draw::Coord<i32>; 4],
+}
+
+impl Curve {
+    pub fn new() -> Self {
+        Self {
+            size: 3,
+            stage: Stage::Idle,
+            points: [draw::Coord(0, 0); 4],
+        }
+    }
+
+    fn place(&mut self, coord: draw::Coord<i32>) {
+        match self.stage {
+            Stage::Idle => {}
+            // Control points stay on the ends while the curve is a straight line
+            Stage::Ends => self.points[2..].fill(coord),
+            // Until the second control point is placed both bend the curve together
+            Stage::FirstControl => self.points[1..3].fill(coord),
+            Stage::SecondControl => self.points[2] = coord,
+        }
+    }
+
+    fn draw_curve(&self, origin: (i32, i32), color: enums::Color) {
+        let [p0, p1, p2, p3] = self
+            .points
+            .map(|p| draw::Coord((origin.0 + p.0) as f64, (origin.1 + p.1) as f64));
+
+        draw::set_draw_color(color);
+        draw::set_line_style(
+            draw::LineStyle::Solid | draw::LineStyle::CapRound | draw::LineStyle::JoinRound,
+            self.size,
+        );
+        draw::begin_line();
+        draw::draw_curve(p0, p1, p2, p3);
+        draw::end_line();
+        draw::set_line_style(draw::LineStyle::Solid, 0);
+    }
+}
+
+impl Tool for Curve {
+    fn name(&self) -> &'static str {
+        "Curve"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'c'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn push(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if self.stage == Stage::Idle {
+            self.stage = Stage::Ends;
+            self.points = [coord; 4];
+        } else {
+            self.place(coord);
+        }
+    }
+
+    fn drag(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.place(coord);
+    }
+
+    fn released(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.place(coord);
+        self.stage = match self.stage {
+            // A click without dragging does not start a curve
+            Stage::Ends if (self.points[0].0, self.points[0].1) == (coord.0, coord.1) => {
+                Stage::Idle
+            }
+            Stage::Ends => Stage::FirstControl,
+            Stage::FirstControl => Stage::SecondControl,
+            Stage::SecondControl => {
+                self.finish(ctx);
+                Stage::Idle
+            }
+            Stage::Idle => Stage::Idle,
+        };
+    }
+
+    fn key(&mut self, _ctx: &mut ToolContext, key: enums::Key) -> bool {
+        if self.stage != Stage::Idle && key == enums::Key::Escape {
+            self.stage = Stage::Idle;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Paints the curve as far as it has been bent, a curve whose ends are
+    /// not placed yet is dropped
+    fn finish(&mut self, ctx: &mut ToolContext) {
+        if matches!(self.stage, Stage::FirstControl | Stage::SecondControl) {
+            // The curve stays inside the hull of its control points
+            let area = points_bounds(&self.points, self.size / 2 + 1);
+            ctx.paint(ctx.fg_color, area, |color, origin| {
+                self.draw_curve(origin, color)
+            });
+        }
+        self.stage = Stage::Idle;
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), _coord: CoordOption) {
+        if self.stage != Stage::Idle {
+            self.draw_curve(origin, ctx.fg_color);
+        }
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
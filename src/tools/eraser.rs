@@ -0,0 +1,86 @@
+use fltk::*;
+
+use super::{offset_coord, points_bounds, CoordOption, Tool, ToolContext};
+
+/// Square eraser that restores the background or transparency
+pub struct Eraser {
+    size: i32,
+    last_coord: CoordOption,
+}
+
+impl Eraser {
+    pub fn new() -> Self {
+        Self {
+            size: 15,
+            last_coord: None,
+        }
+    }
+
+    fn erase_square(&self, coord: draw::Coord<i32>, color: enums::Color) {
+        let r = self.size / 2;
+        draw::draw_rect_fill(coord.0 - r, coord.1 - r, self.size, self.size, color);
+    }
+}
+
+impl Tool for Eraser {
+    fn name(&self) -> &'static str {
+        "Eraser"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'e'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        ctx.erase(
+            points_bounds(&[coord], self.size / 2 + 1),
+            |color, origin| self.erase_square(offset_coord(coord, origin), color),
+        );
+
+        self.last_coord = Some(coord);
+    }
+
+    fn drag(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(c) = self.last_coord {
+            // Sweep the square along the segment so diagonal strokes have no gaps
+            let (dx, dy) = (coord.0 - c.0, coord.1 - c.1);
+            let steps = dx.abs().max(dy.abs()).max(1);
+            let stride = (self.size / 4).max(1);
+            let area = points_bounds(&[c, coord], self.size / 2 + 1);
+            ctx.erase(area, |color, origin| {
+                for i in (0..=steps).step_by(stride as usize).chain([steps]) {
+                    let p = draw::Coord(c.0 + dx * i / steps, c.1 + dy * i / steps);
+                    self.erase_square(offset_coord(p, origin), color);
+                }
+            });
+
+            self.last_coord = Some(coord);
+        }
+    }
+
+    fn released(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {
+        self.last_coord = None;
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        if let Some(c) = coord {
+            let r = self.size / 2;
+            draw::set_draw_color(ctx.overlay_color());
+            draw::set_line_style(draw::LineStyle::Solid, 1);
+            draw::draw_rect(origin.0 + c.0 - r, origin.1 + c.1 - r, self.size, self.size);
+        }
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        // Square outline drawn by the overlay replaces the pointer
+        enums::Cursor::None
+    }
+}
@@ -0,0 +1,87 @@
+use fltk::*;
+
+use super::{Tool, ToolContext, ToolOption};
+
+/// Width of the sampled square for each item of the sample option
+const SAMPLE_SIZES: &[i32] = &[1, 3, 5];
+const SAMPLE_ITEMS: &[&str] = &["Single pixel", "3 x 3 average", "5 x 5 average"];
+
+/// Picks the foreground color with the left button and the background color with the right one
+pub struct Eyedropper {
+    sample: usize,
+}
+
+impl Eyedropper {
+    pub const NAME: &'static str = "Eyedropper";
+
+    pub fn new() -> Self {
+        Self { sample: 0 }
+    }
+
+    fn pick(&self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if coord.0 < 0 || coord.1 < 0 || coord.0 >= ctx.size.0 || coord.1 >= ctx.size.1 {
+            return;
+        }
+        let Some(img) = ctx.read_image() else {
+            return;
+        };
+
+        // Average of the sampled square, clipped to the image
+        let r = SAMPLE_SIZES[self.sample] / 2;
+        let mut sum = [0u32; 3];
+        let mut count = 0;
+        for y in (coord.1 - r).max(0)..=(coord.1 + r).min(ctx.size.1 - 1) {
+            for x in (coord.0 - r).max(0)..=(coord.0 + r).min(ctx.size.0 - 1) {
+                let p = img.get_pixel(x as u32, y as u32).0;
+                for (s, c) in sum.iter_mut().zip(p) {
+                    *s += c as u32;
+                }
+                count += 1;
+            }
+        }
+        let [r, g, b] = sum.map(|s| ((s + count / 2) / count) as u8);
+        let color = enums::Color::from_rgb(r, g, b);
+
+        if app::event_mouse_button() == app::MouseButton::Right {
+            ctx.bg_color = color;
+        } else {
+            ctx.fg_color = color;
+        }
+    }
+}
+
+impl Tool for Eyedropper {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'i'
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![ToolOption::Choice {
+            label: "Sample:",
+            items: SAMPLE_ITEMS.iter().map(|s| s.to_string()).collect(),
+            value: self.sample,
+        }]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        if index == 0 {
+            self.sample = (value as usize).min(SAMPLE_SIZES.len() - 1);
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.pick(ctx, coord);
+    }
+
+    fn drag(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.pick(ctx, coord);
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
@@ -0,0 +1,160 @@
+use ::image::{GrayImage, Luma, Rgb, RgbImage};
+use fltk::*;
+
+use super::{Tool, ToolContext, ToolOption};
+use crate::selection::PixelBlock;
+
+/// Finds pixels whose channels all differ from the pixel at `start` by at most `tolerance`.
+/// Contiguous regions grow from `start` row by row, otherwise every matching pixel is taken.
+/// Returns one flag per pixel, row by row
+pub fn similar_region(
+    img: &RgbImage,
+    alpha: Option<&GrayImage>,
+    start: (u32, u32),
+    tolerance: u8,
+    contiguous: bool,
+) -> Vec<bool> {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let pixel = |x: usize, y: usize| {
+        let c = img.get_pixel(x as u32, y as u32).0;
+        let a = alpha.map_or(255, |alpha| alpha.get_pixel(x as u32, y as u32).0[0]);
+        [c[0], c[1], c[2], a]
+    };
+    let target = pixel(start.0 as usize, start.1 as usize);
+    let matches = |x: usize, y: usize| {
+        pixel(x, y)
+            .iter()
+            .zip(target.iter())
+            .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+    };
+
+    let mut mask = vec![false; w * h];
+    if !contiguous {
+        for y in 0..h {
+            for x in 0..w {
+                mask[y * w + x] = matches(x, y);
+            }
+        }
+        return mask;
+    }
+
+    // Scanline fill, every seed covers a whole horizontal run and seeds the rows around it
+    let mut seeds = vec![(start.0 as usize, start.1 as usize)];
+    while let Some((x, y)) = seeds.pop() {
+        if mask[y * w + x] || !matches(x, y) {
+            continue;
+        }
+
+        let mut left = x;
+        while left > 0 && !mask[y * w + left - 1] && matches(left - 1, y) {
+            left -= 1;
+        }
+        let mut right = x;
+        while right + 1 < w && !mask[y * w + right + 1] && matches(right + 1, y) {
+            right += 1;
+        }
+        mask[y * w + left..=y * w + right].fill(true);
+
+        for ny in [y.wrapping_sub(1), y + 1] {
+            if ny >= h {
+                continue;
+            }
+            let mut in_run = false;
+            for nx in left..=right {
+                let fits = !mask[ny * w + nx] && matches(nx, ny);
+                if fits && !in_run {
+                    seeds.push((nx, ny));
+                }
+                in_run = fits;
+            }
+        }
+    }
+
+    mask
+}
+
+/// Paint bucket that fills an area of similar color with the foreground color
+pub struct Fill {
+    tolerance: u8,
+    contiguous: bool,
+}
+
+impl Fill {
+    pub fn new() -> Self {
+        Self {
+            tolerance: 32,
+            contiguous: true,
+        }
+    }
+}
+
+impl Tool for Fill {
+    fn name(&self) -> &'static str {
+        "Fill"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'f'
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![
+            ToolOption::Number {
+                label: "Tolerance:",
+                min: 0.0,
+                max: 255.0,
+                step: 1.0,
+                value: self.tolerance as f64,
+            },
+            ToolOption::Toggle {
+                label: "Contiguous",
+                value: self.contiguous,
+            },
+        ]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        match index {
+            0 => self.tolerance = value.clamp(0.0, 255.0) as u8,
+            1 => self.contiguous = value != 0.0,
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if coord.0 < 0 || coord.1 < 0 || coord.0 >= ctx.size.0 || coord.1 >= ctx.size.1 {
+            return;
+        }
+        let Some(img) = ctx.read_image() else {
+            return;
+        };
+        let alpha = ctx.read_alpha();
+
+        let mut mask = similar_region(
+            &img,
+            alpha.as_ref(),
+            (coord.0 as u32, coord.1 as u32),
+            self.tolerance,
+            self.contiguous,
+        );
+        for (i, filled) in mask.iter_mut().enumerate() {
+            let (x, y) = (i as i32 % ctx.size.0, i as i32 / ctx.size.0);
+            *filled = *filled && ctx.is_selected(x, y);
+        }
+
+        // Only the filled pixels change, 16-bit documents keep full precision elsewhere
+        let (w, h) = (ctx.size.0 as u32, ctx.size.1 as u32);
+        let mask = GrayImage::from_fn(w, h, |x, y| Luma([mask[(y * w + x) as usize] as u8 * 255]));
+        let (r, g, b) = ctx.fg_color.to_rgb();
+        let block = PixelBlock {
+            image: RgbImage::from_pixel(w, h, Rgb([r, g, b])),
+            alpha: None,
+            mask: Some(mask),
+        };
+        ctx.write_block((0, 0), &block);
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
@@ -0,0 +1,213 @@
+use ::image::{Rgb, RgbImage};
+use fltk::*;
+
+use super::{snap_angle, CoordOption, Tool, ToolContext, ToolOption};
+use crate::selection::PixelBlock;
+
+const MODES: &[&str] = &["Linear", "Radial", "Angular", "Reflected"];
+const REPEATS: &[&str] = &["None", "Repeat", "Mirror"];
+
+/// Ordered dither thresholds, spread over a 4x4 tile
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+#[derive(Copy, Clone)]
+enum Mode {
+    Linear,
+    Radial,
+    Angular,
+    Reflected,
+}
+
+/// How the gradient continues past its end
+#[derive(Copy, Clone)]
+enum Spread {
+    None,
+    Repeat,
+    Mirror,
+}
+
+/// Blend from the foreground to the background color over the whole document or
+/// the selection, dragged from start to end. Shift snaps the direction to 45 degrees
+pub struct Gradient {
+    mode: Mode,
+    spread: Spread,
+    dither: bool,
+    start: CoordOption,
+}
+
+impl Gradient {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Linear,
+            spread: Spread::None,
+            dither: true,
+            start: None,
+        }
+    }
+
+    fn end_point(start: draw::Coord<i32>, coord: draw::Coord<i32>) -> draw::Coord<i32> {
+        if app::is_event_shift() {
+            snap_angle(start, coord, 45.0)
+        } else {
+            coord
+        }
+    }
+
+    /// Position of the pixel along the gradient, 0 at the start and 1 at the end
+    fn position(&self, start: (f64, f64), direction: (f64, f64), p: (f64, f64)) -> f64 {
+        let (dx, dy) = (p.0 - start.0, p.1 - start.1);
+        let length = direction.0.hypot(direction.1);
+        let t = match self.mode {
+            Mode::Linear => (dx * direction.0 + dy * direction.1) / (length * length),
+            Mode::Reflected => ((dx * direction.0 + dy * direction.1) / (length * length)).abs(),
+            Mode::Radial => dx.hypot(dy) / length,
+            // One sweep per turn, the spread decides how the sweep meets itself again
+            Mode::Angular => {
+                let angle = dy.atan2(dx) - direction.1.atan2(direction.0);
+                let t = (angle / std::f64::consts::TAU).rem_euclid(1.0);
+                return match self.spread {
+                    Spread::None | Spread::Repeat => t,
+                    Spread::Mirror => 1.0 - (2.0 * t - 1.0).abs(),
+                };
+            }
+        };
+
+        match self.spread {
+            Spread::None => t.clamp(0.0, 1.0),
+            Spread::Repeat => t.rem_euclid(1.0),
+            Spread::Mirror => {
+                let t = t.rem_euclid(2.0);
+                if t > 1.0 {
+                    2.0 - t
+                } else {
+                    t
+                }
+            }
+        }
+    }
+
+    fn fill(&self, ctx: &ToolContext, start: draw::Coord<i32>, end: draw::Coord<i32>) {
+        let direction = ((end.0 - start.0) as f64, (end.1 - start.1) as f64);
+        if direction == (0.0, 0.0) {
+            return;
+        }
+        let start = (start.0 as f64, start.1 as f64);
+
+        let from = ctx.fg_color.to_rgb();
+        let to = ctx.bg_color.to_rgb();
+        let channel = |a: u8, b: u8, t: f64, threshold: f64| {
+            let value = a as f64 + (b as f64 - a as f64) * t;
+            (value + threshold).floor().clamp(0.0, 255.0) as u8
+        };
+        let image = RgbImage::from_fn(ctx.size.0 as u32, ctx.size.1 as u32, |x, y| {
+            let t = self.position(start, direction, (x as f64 + 0.5, y as f64 + 0.5));
+            // Dithering spreads the rounding between neighboring pixels to hide banding
+            let threshold = if self.dither {
+                (BAYER[y as usize % 4][x as usize % 4] as f64 + 0.5) / 16.0
+            } else {
+                0.5
+            };
+            Rgb([
+                channel(from.0, to.0, t, threshold),
+                channel(from.1, to.1, t, threshold),
+                channel(from.2, to.2, t, threshold),
+            ])
+        });
+
+        let mask = ctx
+            .selection
+            .borrow()
+            .as_ref()
+            .map(|selection| selection.to_mask(ctx.size));
+        let block = PixelBlock {
+            image,
+            alpha: None,
+            mask,
+        };
+        ctx.write_block((0, 0), &block);
+    }
+}
+
+impl Tool for Gradient {
+    fn name(&self) -> &'static str {
+        "Gradient"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'd'
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![
+            ToolOption::Choice {
+                label: "Mode:",
+                items: MODES.iter().map(|s| s.to_string()).collect(),
+                value: self.mode as usize,
+            },
+            ToolOption::Choice {
+                label: "Repeat:",
+                items: REPEATS.iter().map(|s| s.to_string()).collect(),
+                value: self.spread as usize,
+            },
+            ToolOption::Toggle {
+                label: "Dither",
+                value: self.dither,
+            },
+        ]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        match index {
+            0 => {
+                self.mode = match value as usize {
+                    1 => Mode::Radial,
+                    2 => Mode::Angular,
+                    3 => Mode::Reflected,
+                    _ => Mode::Linear,
+                }
+            }
+            1 => {
+                self.spread = match value as usize {
+                    1 => Spread::Repeat,
+                    2 => Spread::Mirror,
+                    _ => Spread::None,
+                }
+            }
+            2 => self.dither = value != 0.0,
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.start = Some(coord);
+    }
+
+    fn released(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(start) = self.start.take() {
+            self.fill(ctx, start, Self::end_point(start, coord));
+        }
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        if let (Some(start), Some(coord)) = (self.start, coord) {
+            let end = Self::end_point(start, coord);
+            draw::set_draw_color(ctx.overlay_color());
+            draw::set_line_style(draw::LineStyle::Solid, 1);
+            draw::draw_line(
+                origin.0 + start.0,
+                origin.1 + start.1,
+                origin.0 + end.0,
+                origin.1 + end.1,
+            );
+            draw::draw_circle(
+                (origin.0 + start.0) as f64,
+                (origin.1 + start.1) as f64,
+                3.0,
+            );
+        }
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
@@ -0,0 +1,161 @@
+use fltk::*;
+
+use super::{add_vertex, move_last_vertex, CoordOption, Tool, ToolContext, ToolOption};
+use crate::selection::{Selection, SelectionOp};
+
+/// Widest feathering of the selection edge, in pixels
+const MAX_FEATHER: i32 = 50;
+
+/// Selection drawn around an area. The freehand lasso follows the pointer while
+/// the button is held, the polygonal lasso joins clicked vertices until a
+/// double-click or Enter, Escape drops it
+pub struct Lasso {
+    polygonal: bool,
+    feather: i32,
+    op: SelectionOp,
+    points: Vec<draw::Coord<i32>>,
+}
+
+impl Lasso {
+    pub fn freehand() -> Self {
+        Self::new(false)
+    }
+
+    pub fn polygonal() -> Self {
+        Self::new(true)
+    }
+
+    fn new(polygonal: bool) -> Self {
+        Self {
+            polygonal,
+            feather: 0,
+            op: SelectionOp::Replace,
+            points: Vec::new(),
+        }
+    }
+
+    /// Turns the outline into a selection combined with the current one
+    fn apply(&mut self, ctx: &ToolContext) {
+        let points = std::mem::take(&mut self.points);
+        let area = Selection::from_polygon(&points, ctx.size)
+            .and_then(|area| area.feathered(self.feather, ctx.size));
+        let current = ctx.selection.borrow().clone();
+        let selection = Selection::combine(current.as_ref(), area, self.op, ctx.size);
+        ctx.selection.replace(selection);
+    }
+}
+
+impl Tool for Lasso {
+    fn name(&self) -> &'static str {
+        if self.polygonal {
+            "Polygonal Lasso"
+        } else {
+            "Lasso"
+        }
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        if self.polygonal {
+            enums::Shortcut::Shift | 'g'
+        } else {
+            enums::Shortcut::None | 'g'
+        }
+    }
+
+    fn uses_alt(&self) -> bool {
+        true
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![ToolOption::Number {
+            label: "Feather:",
+            min: 0.0,
+            max: MAX_FEATHER as f64,
+            step: 1.0,
+            value: self.feather as f64,
+        }]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        if index == 0 {
+            self.feather = value.clamp(0.0, MAX_FEATHER as f64) as i32;
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if self.points.is_empty() {
+            self.op = SelectionOp::from_modifiers();
+        }
+
+        if !self.polygonal {
+            self.points.push(coord);
+        } else if add_vertex(&mut self.points, coord) {
+            self.apply(ctx);
+        }
+    }
+
+    fn drag(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if self.polygonal {
+            move_last_vertex(&mut self.points, coord);
+        } else if self
+            .points
+            .last()
+            .is_none_or(|p| (p.0, p.1) != (coord.0, coord.1))
+        {
+            self.points.push(coord);
+        }
+    }
+
+    fn released(&mut self, ctx: &mut ToolContext, _coord: draw::Coord<i32>) {
+        if !self.polygonal {
+            self.apply(ctx);
+        }
+    }
+
+    fn key(&mut self, ctx: &mut ToolContext, key: enums::Key) -> bool {
+        if self.points.is_empty() {
+            return false;
+        }
+        match key {
+            enums::Key::Enter | enums::Key::KPEnter => {
+                self.apply(ctx);
+                true
+            }
+            enums::Key::Escape => {
+                self.points.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Selects the area of an unfinished polygonal outline, fewer than three
+    /// vertices enclose nothing and are dropped
+    fn finish(&mut self, ctx: &mut ToolContext) {
+        if self.polygonal && self.points.len() > 2 {
+            self.apply(ctx);
+        }
+        self.points.clear();
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        if self.points.is_empty() {
+            return;
+        }
+
+        // The polygonal outline follows the pointer from its last vertex
+        let pointer = if self.polygonal { coord } else { None };
+        draw::set_draw_color(ctx.overlay_color());
+        draw::set_line_style(draw::LineStyle::Dot, 1);
+        draw::begin_loop();
+        for c in self.points.iter().chain(pointer.as_ref()) {
+            draw::vertex((origin.0 + c.0) as f64, (origin.1 + c.1) as f64);
+        }
+        draw::end_loop();
+        draw::set_line_style(draw::LineStyle::Solid, 1);
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
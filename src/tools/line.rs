@@ -0,0 +1,97 @@
+use fltk::*;
+
+use super::{offset_coord, points_bounds, CoordOption, Tool, ToolContext};
+
+/// Angle step of lines drawn with Shift held
+const SNAP_ANGLE: f64 = 15.0;
+
+/// Straight line between the points where the mouse was pressed and released
+pub struct Line {
+    size: i32,
+    start: CoordOption,
+    end: CoordOption,
+}
+
+impl Line {
+    pub fn new() -> Self {
+        Self {
+            size: 3,
+            start: None,
+            end: None,
+        }
+    }
+
+    fn end_point(start: draw::Coord<i32>, coord: draw::Coord<i32>) -> draw::Coord<i32> {
+        if app::is_event_shift() {
+            super::snap_angle(start, coord, SNAP_ANGLE)
+        } else {
+            coord
+        }
+    }
+
+    fn draw_line(&self, start: draw::Coord<i32>, end: draw::Coord<i32>, color: enums::Color) {
+        draw::set_draw_color(color);
+        draw::set_line_style(
+            draw::LineStyle::Solid | draw::LineStyle::CapRound,
+            self.size,
+        );
+        draw::draw_line(start.0, start.1, end.0, end.1);
+        draw::set_line_style(draw::LineStyle::Solid, 0);
+    }
+}
+
+impl Tool for Line {
+    fn name(&self) -> &'static str {
+        "Line"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'l'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn push(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.start = Some(coord);
+        self.end = Some(coord);
+    }
+
+    fn drag(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(start) = self.start {
+            self.end = Some(Self::end_point(start, coord));
+        }
+    }
+
+    fn released(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(start) = self.start.take() {
+            let end = Self::end_point(start, coord);
+            let area = points_bounds(&[start, end], self.size / 2 + 1);
+            ctx.paint(ctx.fg_color, area, |color, origin| {
+                self.draw_line(
+                    offset_coord(start, origin),
+                    offset_coord(end, origin),
+                    color,
+                )
+            });
+        }
+        self.end = None;
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), _coord: CoordOption) {
+        // Rubber band shows the line that is committed on release
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            let (start, end) = (offset_coord(start, origin), offset_coord(end, origin));
+            self.draw_line(start, end, ctx.fg_color);
+        }
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
@@ -0,0 +1,163 @@
+use fltk::*;
+
+use super::{offset_coord, points_bounds, CoordOption, Tool, ToolContext, ToolOption};
+
+/// Pixels on the line from `from` to `to` without anti-aliasing, `from` itself is left out
+fn bresenham(from: draw::Coord<i32>, to: draw::Coord<i32>) -> Vec<draw::Coord<i32>> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y) = (from.0, from.1);
+    let mut error = dx + dy;
+    let mut points = Vec::new();
+    while (x, y) != (to.0, to.1) {
+        let e2 = 2 * error;
+        if e2 >= dy {
+            error += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += sy;
+        }
+        points.push(draw::Coord(x, y));
+    }
+    points
+}
+
+/// Hard edged square pencil that sets whole pixels, for pixel art. Pixel perfect
+/// strokes leave out the inner pixel of L-shaped corners, keeping lines one pixel thin
+pub struct Pencil {
+    size: i32,
+    pixel_perfect: bool,
+    /// Last pixel painted by the stroke
+    last_coord: CoordOption,
+    /// Next pixel of a pixel perfect stroke, painted once the following pixel
+    /// shows it is not a corner
+    pending: CoordOption,
+}
+
+impl Pencil {
+    pub fn new() -> Self {
+        Self {
+            size: 1,
+            pixel_perfect: false,
+            last_coord: None,
+            pending: None,
+        }
+    }
+
+    fn draw_pixel(&self, coord: draw::Coord<i32>, color: enums::Color) {
+        let r = self.size / 2;
+        draw::draw_rect_fill(coord.0 - r, coord.1 - r, self.size, self.size, color);
+    }
+
+    fn paint_pixels(&self, ctx: &ToolContext, pixels: &[draw::Coord<i32>]) {
+        if !pixels.is_empty() {
+            let area = points_bounds(pixels, self.size / 2 + 1);
+            ctx.paint(ctx.fg_color, area, |color, origin| {
+                for p in pixels {
+                    self.draw_pixel(offset_coord(*p, origin), color);
+                }
+            });
+        }
+    }
+
+    /// Whether `corner` is the inner pixel of an L between its neighbors
+    fn is_corner(a: draw::Coord<i32>, corner: draw::Coord<i32>, c: draw::Coord<i32>) -> bool {
+        let touches = |p: draw::Coord<i32>| (p.0 - corner.0).abs() + (p.1 - corner.1).abs() == 1;
+        touches(a) && touches(c) && (a.0 - c.0).abs() == 1 && (a.1 - c.1).abs() == 1
+    }
+}
+
+impl Tool for Pencil {
+    fn name(&self) -> &'static str {
+        "Pencil"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'n'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![ToolOption::Toggle {
+            label: "Pixel perfect",
+            value: self.pixel_perfect,
+        }]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        if index == 0 {
+            self.pixel_perfect = value != 0.0;
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.paint_pixels(ctx, &[coord]);
+        self.last_coord = Some(coord);
+        self.pending = None;
+    }
+
+    fn drag(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        let Some(start) = self.pending.or(self.last_coord) else {
+            return;
+        };
+        let line = bresenham(start, coord);
+        if !self.pixel_perfect {
+            self.paint_pixels(ctx, &line);
+            self.last_coord = Some(coord);
+            return;
+        }
+
+        let mut painted = Vec::new();
+        for p in line {
+            match (self.last_coord, self.pending) {
+                (Some(a), Some(b)) if Self::is_corner(a, b, p) => {}
+                (_, Some(b)) => {
+                    painted.push(b);
+                    self.last_coord = Some(b);
+                }
+                _ => {}
+            }
+            self.pending = Some(p);
+        }
+        self.paint_pixels(ctx, &painted);
+    }
+
+    fn released(&mut self, ctx: &mut ToolContext, _coord: draw::Coord<i32>) {
+        if let Some(p) = self.pending.take() {
+            self.paint_pixels(ctx, &[p]);
+        }
+        self.last_coord = None;
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        // The pending pixel is only painted with the next one
+        if let Some(p) = self.pending {
+            self.draw_pixel(draw::Coord(origin.0 + p.0, origin.1 + p.1), ctx.fg_color);
+        }
+
+        if let Some(c) = coord {
+            let r = self.size / 2;
+            draw::set_draw_color(ctx.overlay_color());
+            draw::set_line_style(draw::LineStyle::Solid, 1);
+            draw::draw_rect(
+                origin.0 + c.0 - r - 1,
+                origin.1 + c.1 - r - 1,
+                self.size + 2,
+                self.size + 2,
+            );
+        }
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
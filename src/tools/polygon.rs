@@ -0,0 +1,155 @@
+use fltk::*;
+
+use super::{
+    add_vertex, move_last_vertex, offset_coord, points_bounds, CoordOption, Tool, ToolContext,
+    ToolOption,
+};
+
+/// Polyline or polygon built from clicked vertices. Double-click or Enter
+/// commits the shape and Escape drops it
+pub struct Polygon {
+    size: i32,
+    closed: bool,
+    filled: bool,
+    points: Vec<draw::Coord<i32>>,
+}
+
+impl Polygon {
+    pub fn new() -> Self {
+        Self {
+            size: 3,
+            closed: true,
+            filled: false,
+            points: Vec::new(),
+        }
+    }
+
+    fn draw_path(&self, points: &[draw::Coord<i32>], color: enums::Color) {
+        draw::set_draw_color(color);
+
+        if self.closed && self.filled && points.len() > 2 {
+            draw::begin_complex_polygon();
+            for p in points {
+                draw::vertex(p.0 as f64, p.1 as f64);
+            }
+            draw::end_complex_polygon();
+        }
+
+        draw::set_line_style(
+            draw::LineStyle::Solid | draw::LineStyle::CapRound | draw::LineStyle::JoinRound,
+            self.size,
+        );
+        if self.closed {
+            draw::begin_loop();
+        } else {
+            draw::begin_line();
+        }
+        for p in points {
+            draw::vertex(p.0 as f64, p.1 as f64);
+        }
+        if self.closed {
+            draw::end_loop();
+        } else {
+            draw::end_line();
+        }
+        draw::set_line_style(draw::LineStyle::Solid, 0);
+    }
+}
+
+impl Tool for Polygon {
+    fn name(&self) -> &'static str {
+        "Polygon"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'p'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![
+            ToolOption::Toggle {
+                label: "Closed",
+                value: self.closed,
+            },
+            ToolOption::Toggle {
+                label: "Filled",
+                value: self.filled,
+            },
+        ]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        match index {
+            0 => self.closed = value != 0.0,
+            1 => self.filled = value != 0.0,
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if add_vertex(&mut self.points, coord) {
+            self.finish(ctx);
+        }
+    }
+
+    fn drag(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        move_last_vertex(&mut self.points, coord);
+    }
+
+    fn key(&mut self, ctx: &mut ToolContext, key: enums::Key) -> bool {
+        if self.points.is_empty() {
+            return false;
+        }
+        match key {
+            enums::Key::Enter | enums::Key::KPEnter => {
+                self.finish(ctx);
+                true
+            }
+            enums::Key::Escape => {
+                self.points.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Paints the shape built so far, a single vertex is dropped
+    fn finish(&mut self, ctx: &mut ToolContext) {
+        let points = std::mem::take(&mut self.points);
+        if points.len() > 1 {
+            let area = points_bounds(&points, self.size / 2 + 1);
+            ctx.paint(ctx.fg_color, area, |color, origin| {
+                let points = points.iter().map(|p| offset_coord(*p, origin));
+                self.draw_path(&points.collect::<Vec<_>>(), color)
+            });
+        }
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        if self.points.is_empty() {
+            return;
+        }
+
+        // Rubber band from the last vertex follows the pointer
+        let points = self
+            .points
+            .iter()
+            .copied()
+            .chain(coord)
+            .map(|c| offset_coord(c, origin))
+            .collect::<Vec<_>>();
+        self.draw_path(&points, ctx.fg_color);
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
@@ -0,0 +1,263 @@
+use ::image::{imageops, GrayImage, Luma, RgbImage};
+use fltk::*;
+
+use super::{mix, segment_distance, CoordOption, Tool, ToolContext, ToolOption};
+use crate::selection::PixelBlock;
+
+#[derive(Copy, Clone)]
+pub enum RetouchKind {
+    /// Drags colors along the stroke
+    Smudge,
+    Blur,
+    Sharpen,
+}
+
+/// Brush that changes the pixels under the stroke instead of painting a color.
+/// Strength is how far each pass goes, in percent
+pub struct Retouch {
+    kind: RetouchKind,
+    size: i32,
+    strength: i32,
+    /// Colors picked up by the smudge brush, one dab in size
+    carried: Option<RgbImage>,
+    /// Document as the stroke has left it so far, read once when the stroke starts
+    snapshot: Option<(RgbImage, Option<GrayImage>)>,
+    last_coord: CoordOption,
+}
+
+impl Retouch {
+    pub fn new(kind: RetouchKind) -> Self {
+        Self {
+            kind,
+            size: 20,
+            strength: 50,
+            carried: None,
+            snapshot: None,
+            last_coord: None,
+        }
+    }
+
+    /// Strength scaled to a channel value
+    fn amount(&self) -> u8 {
+        (self.strength * 255 / 100) as u8
+    }
+
+    /// Top left corner of the dab centered on `coord`
+    fn dab_origin(&self, coord: draw::Coord<i32>) -> (i32, i32) {
+        (coord.0 - self.size / 2, coord.1 - self.size / 2)
+    }
+
+    fn in_dab(&self, i: i32, j: i32) -> bool {
+        let center = (self.size / 2) as f64;
+        let radius = self.size as f64 / 2.0;
+        (i as f64 - center).hypot(j as f64 - center) <= radius
+    }
+
+    /// Picks up the colors under a dab, pixels outside of the document repeat its edge
+    fn pick_up(&mut self, img: &RgbImage, coord: draw::Coord<i32>) {
+        let (ox, oy) = self.dab_origin(coord);
+        let (w, h) = (img.width() as i32, img.height() as i32);
+        let d = self.size as u32 + 1;
+        self.carried = Some(RgbImage::from_fn(d, d, |i, j| {
+            let x = (ox + i as i32).clamp(0, w - 1);
+            let y = (oy + j as i32).clamp(0, h - 1);
+            *img.get_pixel(x as u32, y as u32)
+        }));
+    }
+
+    /// Smudges dabs along the segment into `region`, whose top left corner is at `left`
+    /// and `top`. Changed pixels get marked in `touched`
+    fn smudge(
+        &mut self,
+        region: &mut RgbImage,
+        touched: &mut GrayImage,
+        (left, top): (i32, i32),
+        from: draw::Coord<i32>,
+        to: draw::Coord<i32>,
+    ) {
+        let amount = self.amount();
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let steps = dx.abs().max(dy.abs());
+        if steps == 0 {
+            return;
+        }
+        let stride = (self.size / 8).max(1);
+        let dabs = (stride..steps)
+            .step_by(stride as usize)
+            .chain([steps])
+            .map(|i| draw::Coord(from.0 + dx * i / steps, from.1 + dy * i / steps))
+            .collect::<Vec<_>>();
+
+        for coord in dabs {
+            let (ox, oy) = self.dab_origin(coord);
+            for j in 0..=self.size {
+                for i in 0..=self.size {
+                    let (x, y) = (ox + i - left, oy + j - top);
+                    let outside =
+                        x < 0 || y < 0 || x >= region.width() as i32 || y >= region.height() as i32;
+                    if outside || !self.in_dab(i, j) {
+                        continue;
+                    }
+                    let Some(carried) = self.carried.as_mut() else {
+                        return;
+                    };
+
+                    // Paint left behind is what the brush carries on
+                    let pixel = region.get_pixel_mut(x as u32, y as u32);
+                    let held = carried.get_pixel_mut(i as u32, j as u32);
+                    for c in 0..3 {
+                        pixel.0[c] = mix(pixel.0[c], held.0[c], amount);
+                    }
+                    *held = *pixel;
+                    touched.put_pixel(x as u32, y as u32, Luma([255]));
+                }
+            }
+        }
+    }
+
+    /// Retouches the pixels under the stroke segment from `from` to `to`
+    fn stroke(&mut self, ctx: &ToolContext, from: draw::Coord<i32>, to: draw::Coord<i32>) {
+        let Some((left, top, w, h)) = ctx.stroke_bounds(from, to, self.size) else {
+            return;
+        };
+        let Some((mut img, alpha)) = self.snapshot.take() else {
+            return;
+        };
+        let (w, h) = (w as u32, h as u32);
+
+        let (image, mut mask) = match self.kind {
+            RetouchKind::Smudge => {
+                let mut region = imageops::crop_imm(&img, left as u32, top as u32, w, h).to_image();
+                let mut touched = GrayImage::new(w, h);
+                self.smudge(&mut region, &mut touched, (left, top), from, to);
+                (region, touched)
+            }
+            RetouchKind::Blur | RetouchKind::Sharpen => {
+                let kernel: [f32; 9] = match self.kind {
+                    RetouchKind::Sharpen => [0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0],
+                    _ => [1.0; 9],
+                };
+
+                // One pixel margin lets the filter see the neighbors of the edge pixels,
+                // the document edge repeats outwards
+                let region = RgbImage::from_fn(w + 2, h + 2, |i, j| {
+                    let x = (left + i as i32 - 1).clamp(0, ctx.size.0 - 1);
+                    let y = (top + j as i32 - 1).clamp(0, ctx.size.1 - 1);
+                    *img.get_pixel(x as u32, y as u32)
+                });
+                let filtered = imageops::filter3x3(&region, &kernel);
+                let filtered = imageops::crop_imm(&filtered, 1, 1, w, h).to_image();
+
+                let (a, b) = ((from.0 as f64, from.1 as f64), (to.0 as f64, to.1 as f64));
+                let radius = self.size as f64 / 2.0;
+                let amount = self.amount();
+                let mask = GrayImage::from_fn(w, h, |px, py| {
+                    let p = ((left + px as i32) as f64, (top + py as i32) as f64);
+                    Luma([(segment_distance(p, a, b) <= radius) as u8 * amount])
+                });
+                (filtered, mask)
+            }
+        };
+
+        // Selected coverage limits the change, opacity stays as it is
+        let selection = ctx.selection.borrow();
+        if let Some(selection) = selection.as_ref() {
+            for (px, py, m) in mask.enumerate_pixels_mut() {
+                let coverage = selection.coverage(left + px as i32, top + py as i32);
+                m.0[0] = (m.0[0] as u32 * coverage as u32 / 255) as u8;
+            }
+        }
+        drop(selection);
+
+        for (px, py, m) in mask.enumerate_pixels() {
+            let src = image.get_pixel(px, py);
+            let dst = img.get_pixel_mut(left as u32 + px, top as u32 + py);
+            for c in 0..3 {
+                dst.0[c] = mix(dst.0[c], src.0[c], m.0[0]);
+            }
+        }
+
+        let block = PixelBlock {
+            image: imageops::crop_imm(&img, left as u32, top as u32, w, h).to_image(),
+            alpha: alpha
+                .as_ref()
+                .map(|alpha| imageops::crop_imm(alpha, left as u32, top as u32, w, h).to_image()),
+            mask: None,
+        };
+        ctx.write_block((left, top), &block);
+        self.snapshot = Some((img, alpha));
+    }
+}
+
+impl Tool for Retouch {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            RetouchKind::Smudge => "Smudge",
+            RetouchKind::Blur => "Blur",
+            RetouchKind::Sharpen => "Sharpen",
+        }
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        match self.kind {
+            RetouchKind::Smudge => enums::Shortcut::None | 'm',
+            RetouchKind::Blur => enums::Shortcut::None | 'y',
+            RetouchKind::Sharpen => enums::Shortcut::None | 'h',
+        }
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![ToolOption::Number {
+            label: "Strength:",
+            min: 1.0,
+            max: 100.0,
+            step: 1.0,
+            value: self.strength as f64,
+        }]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        if index == 0 {
+            self.strength = value.clamp(1.0, 100.0) as i32;
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        let Some(img) = ctx.read_image() else {
+            return;
+        };
+        if let RetouchKind::Smudge = self.kind {
+            self.pick_up(&img, coord);
+        }
+        self.snapshot = Some((img, ctx.read_alpha()));
+        if let RetouchKind::Blur | RetouchKind::Sharpen = self.kind {
+            self.stroke(ctx, coord, coord);
+        }
+        self.last_coord = Some(coord);
+    }
+
+    fn drag(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(c) = self.last_coord {
+            self.stroke(ctx, c, coord);
+            self.last_coord = Some(coord);
+        }
+    }
+
+    fn released(&mut self, _ctx: &mut ToolContext, _coord: draw::Coord<i32>) {
+        self.last_coord = None;
+        self.carried = None;
+        self.snapshot = None;
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        ctx.draw_size_circle(origin, coord, self.size);
+    }
+}
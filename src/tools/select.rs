@@ -0,0 +1,185 @@
+use fltk::*;
+
+use super::{CoordOption, Tool, ToolContext};
+use crate::selection::{PixelBlock, Selection, SelectionOp};
+
+/// Distance of an arrow key nudge with Shift held
+const LARGE_NUDGE: i32 = 10;
+
+/// Selected pixels being dragged to a new place
+struct Floating {
+    block: PixelBlock,
+    /// Selection at the place the pixels were taken from
+    selection: Selection,
+    grab: draw::Coord<i32>,
+    offset: (i32, i32),
+}
+
+/// Takes the selected pixels out of the document, leaving the background behind
+fn lift(ctx: &ToolContext) -> Option<(PixelBlock, Selection)> {
+    let selection = ctx.selection.borrow().as_ref()?.clipped(ctx.size)?;
+    let block = PixelBlock {
+        mask: selection.mask.clone(),
+        ..ctx.read_block(selection.bounds)?
+    };
+
+    let (x, y, w, h) = selection.bounds;
+    ctx.erase(selection.bounds, |color, origin| {
+        draw::draw_rect_fill(x + origin.0, y + origin.1, w, h, color)
+    });
+
+    Some((block, selection))
+}
+
+/// Puts lifted pixels back moved by `offset`, the selection follows them
+fn drop_block(ctx: &ToolContext, block: &PixelBlock, selection: &Selection, offset: (i32, i32)) {
+    let moved = selection.translated(offset.0, offset.1);
+    ctx.write_block((moved.bounds.0, moved.bounds.1), block);
+    ctx.selection.replace(moved.clipped(ctx.size));
+}
+
+/// Rectangular marquee. Dragging inside the selection moves the selected pixels,
+/// arrow keys nudge them by one pixel or by ten with Shift
+pub struct RectangleSelect {
+    anchor: CoordOption,
+    op: SelectionOp,
+    floating: Option<Floating>,
+    over_selection: bool,
+}
+
+impl RectangleSelect {
+    pub fn new() -> Self {
+        Self {
+            anchor: None,
+            op: SelectionOp::Replace,
+            floating: None,
+            over_selection: false,
+        }
+    }
+}
+
+impl Tool for RectangleSelect {
+    fn name(&self) -> &'static str {
+        "Rectangle Select"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 's'
+    }
+
+    fn uses_alt(&self) -> bool {
+        true
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.op = SelectionOp::from_modifiers();
+        let inside = self.op == SelectionOp::Replace
+            && ctx
+                .selection
+                .borrow()
+                .as_ref()
+                .is_some_and(|s| s.contains(coord.0, coord.1));
+
+        if inside {
+            if let Some((block, selection)) = lift(ctx) {
+                self.floating = Some(Floating {
+                    block,
+                    selection,
+                    grab: coord,
+                    offset: (0, 0),
+                });
+            }
+        } else {
+            self.anchor = Some(coord);
+            if self.op == SelectionOp::Replace {
+                ctx.selection.replace(None);
+            }
+        }
+    }
+
+    fn drag(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(floating) = self.floating.as_mut() {
+            floating.offset = (coord.0 - floating.grab.0, coord.1 - floating.grab.1);
+            let (dx, dy) = floating.offset;
+            ctx.selection
+                .replace(Some(floating.selection.translated(dx, dy)));
+        } else if let (Some(anchor), SelectionOp::Replace) = (self.anchor, self.op) {
+            let selection = Selection::from_corners(anchor, coord).clipped(ctx.size);
+            ctx.selection.replace(selection);
+        }
+    }
+
+    fn released(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(floating) = self.floating.take() {
+            drop_block(ctx, &floating.block, &floating.selection, floating.offset);
+        } else if let Some(anchor) = self.anchor.take() {
+            // A click without dragging only removes the selection
+            if (anchor.0, anchor.1) != (coord.0, coord.1) {
+                let rectangle = Selection::from_corners(anchor, coord).clipped(ctx.size);
+                let current = ctx.selection.borrow().clone();
+                let selection = Selection::combine(current.as_ref(), rectangle, self.op, ctx.size);
+                ctx.selection.replace(selection);
+            }
+        }
+    }
+
+    fn moved(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.over_selection = ctx
+            .selection
+            .borrow()
+            .as_ref()
+            .is_some_and(|s| s.contains(coord.0, coord.1));
+    }
+
+    fn key(&mut self, ctx: &mut ToolContext, key: enums::Key) -> bool {
+        let step = if app::is_event_shift() {
+            LARGE_NUDGE
+        } else {
+            1
+        };
+        let offset = match key {
+            enums::Key::Left => (-step, 0),
+            enums::Key::Right => (step, 0),
+            enums::Key::Up => (0, -step),
+            enums::Key::Down => (0, step),
+            _ => return false,
+        };
+
+        match lift(ctx) {
+            Some((block, selection)) => {
+                drop_block(ctx, &block, &selection, offset);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), coord: CoordOption) {
+        // Rectangles combined with the selection only apply on release
+        if let (Some(anchor), Some(coord)) = (self.anchor, coord) {
+            if self.op != SelectionOp::Replace {
+                let (x, y, w, h) = Selection::from_corners(anchor, coord).bounds;
+                draw::set_draw_color(ctx.overlay_color());
+                draw::set_line_style(draw::LineStyle::Dot, 1);
+                draw::draw_rect(origin.0 + x, origin.1 + y, w, h);
+                draw::set_line_style(draw::LineStyle::Solid, 1);
+            }
+        }
+
+        if let Some(floating) = &self.floating {
+            let (x, y, _, _) = floating.selection.bounds;
+            floating.block.draw(
+                origin.0 + x + floating.offset.0,
+                origin.1 + y + floating.offset.1,
+            );
+        }
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        if self.over_selection {
+            enums::Cursor::Move
+        } else {
+            enums::Cursor::Cross
+        }
+    }
+}
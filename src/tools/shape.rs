@@ -0,0 +1,209 @@
+use fltk::*;
+
+use super::{Bounds, CoordOption, Tool, ToolContext, ToolOption};
+
+/// Largest corner radius of rounded rectangles
+const CORNER_RADIUS: i32 = 16;
+
+const FILL_MODES: &[&str] = &["Outline", "Fill", "Outline and fill"];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ShapeKind {
+    Rectangle,
+    RoundedRectangle,
+    Ellipse,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum FillMode {
+    /// Outline in the foreground color
+    Outline,
+    /// Interior in the foreground color
+    Fill,
+    /// Outline in the foreground color around interior in the background color
+    OutlineFill,
+}
+
+/// Shape dragged from corner to corner, Shift makes it square or round
+/// and Alt puts the center at the start point
+pub struct Shape {
+    kind: ShapeKind,
+    size: i32,
+    mode: FillMode,
+    start: CoordOption,
+    bounds: Option<Bounds>,
+}
+
+impl Shape {
+    pub fn new(kind: ShapeKind) -> Self {
+        Self {
+            kind,
+            size: 3,
+            mode: FillMode::Outline,
+            start: None,
+            bounds: None,
+        }
+    }
+
+    fn shape_bounds(start: draw::Coord<i32>, coord: draw::Coord<i32>) -> Bounds {
+        let (mut dx, mut dy) = (coord.0 - start.0, coord.1 - start.1);
+        if app::is_event_shift() {
+            let side = dx.abs().max(dy.abs());
+            dx = if dx < 0 { -side } else { side };
+            dy = if dy < 0 { -side } else { side };
+        }
+
+        if app::is_event_alt() {
+            let (rx, ry) = (dx.abs(), dy.abs());
+            (start.0 - rx, start.1 - ry, 2 * rx + 1, 2 * ry + 1)
+        } else {
+            (
+                start.0.min(start.0 + dx),
+                start.1.min(start.1 + dy),
+                dx.abs() + 1,
+                dy.abs() + 1,
+            )
+        }
+    }
+
+    fn corner_radius(w: i32, h: i32) -> i32 {
+        (w.min(h) / 2).min(CORNER_RADIUS)
+    }
+
+    fn draw_fill(&self, (x, y, w, h): Bounds, color: enums::Color) {
+        draw::set_draw_color(color);
+        match self.kind {
+            ShapeKind::Rectangle => draw::draw_rectf(x, y, w, h),
+            ShapeKind::RoundedRectangle => {
+                draw::draw_rounded_rectf(x, y, w, h, Self::corner_radius(w, h))
+            }
+            ShapeKind::Ellipse => draw::draw_pie(x, y, w, h, 0.0, 360.0),
+        }
+    }
+
+    fn draw_outline(&self, (x, y, w, h): Bounds, color: enums::Color) {
+        // Keep the whole stroke inside the dragged area
+        let inset = self.size / 2;
+        let (x, y) = (x + inset, y + inset);
+        let (w, h) = ((w - 2 * inset).max(1), (h - 2 * inset).max(1));
+
+        draw::set_draw_color(color);
+        draw::set_line_style(draw::LineStyle::Solid, self.size);
+        match self.kind {
+            ShapeKind::Rectangle => {
+                draw::begin_loop();
+                draw::vertex(x as f64, y as f64);
+                draw::vertex((x + w - 1) as f64, y as f64);
+                draw::vertex((x + w - 1) as f64, (y + h - 1) as f64);
+                draw::vertex(x as f64, (y + h - 1) as f64);
+                draw::end_loop();
+            }
+            ShapeKind::RoundedRectangle => {
+                draw::draw_rounded_rect(x, y, w, h, Self::corner_radius(w, h))
+            }
+            ShapeKind::Ellipse => draw::draw_arc(x, y, w, h, 0.0, 360.0),
+        }
+        draw::set_line_style(draw::LineStyle::Solid, 0);
+    }
+
+    fn draw_shape(&self, ctx: &ToolContext, bounds: Bounds) {
+        // Outlines stay inside the dragged area, the margin covers the ends of the stroke
+        let area = (bounds.0 - 1, bounds.1 - 1, bounds.2 + 2, bounds.3 + 2);
+        let moved = |(x, y, w, h): Bounds, origin: (i32, i32)| (x + origin.0, y + origin.1, w, h);
+        let outline = |color, origin| self.draw_outline(moved(bounds, origin), color);
+        let fill = |color, origin| self.draw_fill(moved(bounds, origin), color);
+        match self.mode {
+            FillMode::Outline => ctx.paint(ctx.fg_color, area, outline),
+            FillMode::Fill => ctx.paint(ctx.fg_color, area, fill),
+            FillMode::OutlineFill => {
+                ctx.paint(ctx.bg_color, area, fill);
+                ctx.paint(ctx.fg_color, area, outline);
+            }
+        }
+    }
+}
+
+impl Tool for Shape {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            ShapeKind::Rectangle => "Rectangle",
+            ShapeKind::RoundedRectangle => "Rounded Rectangle",
+            ShapeKind::Ellipse => "Ellipse",
+        }
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        match self.kind {
+            ShapeKind::Rectangle => enums::Shortcut::None | 'r',
+            ShapeKind::RoundedRectangle => enums::Shortcut::None | 'u',
+            ShapeKind::Ellipse => enums::Shortcut::None | 'o',
+        }
+    }
+
+    fn uses_alt(&self) -> bool {
+        true
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![ToolOption::Choice {
+            label: "Mode:",
+            items: FILL_MODES.iter().map(|s| s.to_string()).collect(),
+            value: self.mode as usize,
+        }]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        if index == 0 {
+            self.mode = match value as usize {
+                1 => FillMode::Fill,
+                2 => FillMode::OutlineFill,
+                _ => FillMode::Outline,
+            };
+        }
+    }
+
+    fn push(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.start = Some(coord);
+        self.bounds = Some(Self::shape_bounds(coord, coord));
+    }
+
+    fn drag(&mut self, _ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(start) = self.start {
+            self.bounds = Some(Self::shape_bounds(start, coord));
+        }
+    }
+
+    fn released(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if let Some(start) = self.start.take() {
+            self.draw_shape(ctx, Self::shape_bounds(start, coord));
+        }
+        self.bounds = None;
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), _coord: CoordOption) {
+        // Preview of the shape that is committed on release
+        if let Some((x, y, w, h)) = self.bounds {
+            let bounds = (origin.0 + x, origin.1 + y, w, h);
+            match self.mode {
+                FillMode::Outline => self.draw_outline(bounds, ctx.fg_color),
+                FillMode::Fill => self.draw_fill(bounds, ctx.fg_color),
+                FillMode::OutlineFill => {
+                    self.draw_fill(bounds, ctx.bg_color);
+                    self.draw_outline(bounds, ctx.fg_color);
+                }
+            }
+        }
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}
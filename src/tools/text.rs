@@ -0,0 +1,227 @@
+use fltk::*;
+
+use super::{offset_coord, CoordOption, Tool, ToolContext, ToolOption};
+
+/// Font family of the system with the indexes of its regular, bold, italic
+/// and bold italic faces in the FLTK font list
+struct FontFamily {
+    name: String,
+    faces: [Option<usize>; 4],
+}
+
+/// Groups the fonts loaded by FLTK into families. FLTK names the faces with a
+/// leading space, B, I or P for regular, bold, italic and bold italic on systems
+/// where the first font name starts with a space, elsewhere each font is a family
+fn font_families() -> Vec<FontFamily> {
+    let fonts = app::fonts();
+    let styled = fonts.first().is_some_and(|name| name.starts_with(' '));
+
+    let mut families: Vec<FontFamily> = Vec::new();
+    for (index, font) in fonts.iter().enumerate() {
+        let (face, name) = match font.split_at_checked(1) {
+            Some((" ", name)) if styled => (0, name),
+            Some(("B", name)) if styled => (1, name),
+            Some(("I", name)) if styled => (2, name),
+            Some(("P", name)) if styled => (3, name),
+            _ => (0, font.as_str()),
+        };
+        match families.iter_mut().find(|family| family.name == name) {
+            Some(family) => family.faces[face] = family.faces[face].or(Some(index)),
+            None => {
+                let mut faces = [None; 4];
+                faces[face] = Some(index);
+                families.push(FontFamily {
+                    name: name.to_string(),
+                    faces,
+                });
+            }
+        }
+    }
+    families
+}
+
+/// Text typed into a box on the canvas. The text stays editable until a click
+/// elsewhere, Ctrl+Enter or another tool commits it, Escape drops it
+pub struct Text {
+    size: i32,
+    families: Vec<FontFamily>,
+    family: usize,
+    bold: bool,
+    italic: bool,
+    /// Top left corner of the text box being edited
+    position: CoordOption,
+    text: String,
+}
+
+impl Text {
+    pub fn new() -> Self {
+        Self {
+            size: 24,
+            families: font_families(),
+            family: 0,
+            bold: false,
+            italic: false,
+            position: None,
+            text: String::new(),
+        }
+    }
+
+    fn font(&self) -> enums::Font {
+        let Some(family) = self.families.get(self.family) else {
+            return enums::Font::Helvetica;
+        };
+        // Families without the asked face use one they have
+        let face = self.bold as usize + 2 * self.italic as usize;
+        let index = family.faces[face].or_else(|| family.faces.iter().flatten().next().copied());
+        index.map_or(enums::Font::Helvetica, enums::Font::by_index)
+    }
+
+    /// Draws the text with its top left corner at `position`, returns the size of the text
+    fn draw_text(&self, position: draw::Coord<i32>, color: enums::Color) -> (i32, i32) {
+        draw::set_font(self.font(), self.size);
+        draw::set_draw_color(color);
+
+        let line_height = draw::height();
+        let mut width = 0;
+        let mut y = position.1;
+        for line in self.text.split('\n') {
+            draw::draw_text(line, position.0, y + line_height - draw::descent());
+            width = width.max(draw::width(line).ceil() as i32);
+            y += line_height;
+        }
+        (width, y - position.1)
+    }
+
+    /// Size of the text in its font, without drawing it
+    fn text_size(&self) -> (i32, i32) {
+        draw::set_font(self.font(), self.size);
+        let lines = self.text.split('\n');
+        let width = lines
+            .clone()
+            .map(|line| draw::width(line).ceil() as i32)
+            .max();
+        (width.unwrap_or(0), lines.count() as i32 * draw::height())
+    }
+
+    fn commit(&mut self, ctx: &ToolContext) {
+        if let Some(position) = self.position.take() {
+            if !self.text.is_empty() {
+                // Slanted and overhanging glyphs may reach past the measured size
+                let (w, h) = self.text_size();
+                let margin = self.size / 2 + 1;
+                let (x, y) = (position.0 - margin, position.1 - margin);
+                let area = (x, y, w + 2 * margin, h + 2 * margin);
+                ctx.paint(ctx.fg_color, area, |color, origin| {
+                    self.draw_text(offset_coord(position, origin), color);
+                });
+            }
+        }
+        self.text.clear();
+    }
+}
+
+impl Tool for Text {
+    fn name(&self) -> &'static str {
+        "Text"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 't'
+    }
+
+    fn size(&self) -> Option<i32> {
+        Some(self.size)
+    }
+
+    fn set_size(&mut self, size: i32) {
+        self.size = size;
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![
+            ToolOption::Choice {
+                label: "Font:",
+                items: self.families.iter().map(|f| f.name.clone()).collect(),
+                value: self.family,
+            },
+            ToolOption::Toggle {
+                label: "Bold",
+                value: self.bold,
+            },
+            ToolOption::Toggle {
+                label: "Italic",
+                value: self.italic,
+            },
+        ]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        match index {
+            0 => self.family = (value as usize).min(self.families.len().saturating_sub(1)),
+            1 => self.bold = value != 0.0,
+            2 => self.italic = value != 0.0,
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        self.commit(ctx);
+        self.position = Some(coord);
+    }
+
+    fn key(&mut self, ctx: &mut ToolContext, key: enums::Key) -> bool {
+        if self.position.is_none() {
+            return false;
+        }
+
+        match key {
+            enums::Key::Escape => {
+                self.position = None;
+                self.text.clear();
+            }
+            enums::Key::Enter | enums::Key::KPEnter if app::is_event_ctrl() => self.commit(ctx),
+            enums::Key::Enter | enums::Key::KPEnter => self.text.push('\n'),
+            enums::Key::BackSpace => {
+                self.text.pop();
+            }
+            // Other shortcuts keep working while typing
+            _ if app::is_event_ctrl() || app::is_event_alt() => return false,
+            _ => {
+                let typed = app::event_text();
+                if typed.is_empty() || typed.chars().any(char::is_control) {
+                    return false;
+                }
+                self.text.push_str(&typed);
+            }
+        }
+        true
+    }
+
+    fn finish(&mut self, ctx: &mut ToolContext) {
+        self.commit(ctx);
+    }
+
+    fn draw_overlay(&self, ctx: &ToolContext, origin: (i32, i32), _coord: CoordOption) {
+        let Some(position) = self.position else {
+            return;
+        };
+
+        let position = draw::Coord(origin.0 + position.0, origin.1 + position.1);
+        let (w, h) = self.draw_text(position, ctx.fg_color);
+
+        // Frame around the text with the caret at its end
+        draw::set_draw_color(ctx.overlay_color());
+        draw::set_line_style(draw::LineStyle::Dot, 1);
+        draw::draw_rect(position.0 - 2, position.1 - 2, w + 6, h + 4);
+        draw::set_line_style(draw::LineStyle::Solid, 1);
+
+        let last_line = self.text.rsplit('\n').next().unwrap_or_default();
+        let caret_x = position.0 + draw::width(last_line).ceil() as i32;
+        let caret_y = position.1 + h - draw::height();
+        draw::draw_line(caret_x, caret_y, caret_x, caret_y + draw::height() - 1);
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Insert
+    }
+}
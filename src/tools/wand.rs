@@ -0,0 +1,91 @@
+use ::image::{GrayImage, Luma};
+use fltk::*;
+
+use super::{fill::similar_region, Tool, ToolContext, ToolOption};
+use crate::selection::{Selection, SelectionOp};
+
+/// Selects the area of similar color around the clicked pixel, or every similar pixel
+/// when not contiguous
+pub struct MagicWand {
+    tolerance: u8,
+    contiguous: bool,
+}
+
+impl MagicWand {
+    pub fn new() -> Self {
+        Self {
+            tolerance: 32,
+            contiguous: true,
+        }
+    }
+}
+
+impl Tool for MagicWand {
+    fn name(&self) -> &'static str {
+        "Magic Wand"
+    }
+
+    fn shortcut(&self) -> enums::Shortcut {
+        enums::Shortcut::None | 'w'
+    }
+
+    fn uses_alt(&self) -> bool {
+        true
+    }
+
+    fn options(&self) -> Vec<ToolOption> {
+        vec![
+            ToolOption::Number {
+                label: "Tolerance:",
+                min: 0.0,
+                max: 255.0,
+                step: 1.0,
+                value: self.tolerance as f64,
+            },
+            ToolOption::Toggle {
+                label: "Contiguous",
+                value: self.contiguous,
+            },
+        ]
+    }
+
+    fn set_option(&mut self, index: usize, value: f64) {
+        match index {
+            0 => self.tolerance = value.clamp(0.0, 255.0) as u8,
+            1 => self.contiguous = value != 0.0,
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, ctx: &mut ToolContext, coord: draw::Coord<i32>) {
+        if coord.0 < 0 || coord.1 < 0 || coord.0 >= ctx.size.0 || coord.1 >= ctx.size.1 {
+            return;
+        }
+        let Some(img) = ctx.read_image() else {
+            return;
+        };
+        let alpha = ctx.read_alpha();
+
+        let region = similar_region(
+            &img,
+            alpha.as_ref(),
+            (coord.0 as u32, coord.1 as u32),
+            self.tolerance,
+            self.contiguous,
+        );
+        let width = ctx.size.0 as u32;
+        let mask = GrayImage::from_fn(width, ctx.size.1 as u32, |x, y| {
+            Luma([region[(y * width + x) as usize] as u8 * 255])
+        });
+
+        let op = SelectionOp::from_modifiers();
+        let current = ctx.selection.borrow().clone();
+        let selection =
+            Selection::combine(current.as_ref(), Selection::from_mask(&mask), op, ctx.size);
+        ctx.selection.replace(selection);
+    }
+
+    fn cursor(&self) -> enums::Cursor {
+        enums::Cursor::Cross
+    }
+}